@@ -1,51 +1,358 @@
+exception Overloaded
+
 module Runtime = struct
   type t =
     { executor : Stubs.Executor.t
     ; notification : int
+    ; reactor_events : Lwt_engine.event list
+    ; iter_hooks :
+        (Lwt_main.Enter_iter_hooks.hook * Lwt_main.Leave_iter_hooks.hook) option
     }
 
   (* For OCaml 5 this should be domain-local storage, for OCaml 4 global ref is
      fine, as in OCaml 4 there's only one domain *)
   let current = ref None
 
-  let create () =
+  let create ~single_threaded ~timer_interval ~iteration_hooks =
     let notification = Lwt_unix.make_notification ~once:false Fun.id in
-    let executor = Stubs.Executor.create notification in
-    Lwt_unix.set_notification notification (fun () -> Stubs.Executor.run_pending executor);
-    Stubs.Executor.run_pending executor;
-    let t = { executor; notification } in
+    let executor =
+      if single_threaded
+      then (
+        match Stubs.Executor.create_single_threaded notification with
+        | Ok executor -> executor
+        | Error msg ->
+          Lwt_unix.stop_notification notification;
+          failwith ("Rust_async.Runtime.init: " ^ msg))
+      else Stubs.Executor.create notification
+    in
+    let run_pending () = Stubs.Executor.run_pending executor in
+    Lwt_unix.set_notification notification run_pending;
+    let reactor_events =
+      if single_threaded
+      then (
+        (* Tokio timers fire only when the reactor is turned, and Tokio does not
+           expose its next deadline, so it's turned periodically in addition to
+           whenever it has pending I/O events *)
+        let timer = Lwt_engine.on_timer timer_interval true (fun _ -> run_pending ()) in
+        match Stubs.Executor.reactor_fd executor with
+        | Some fd ->
+          (* [Unix.file_descr] is [int] on Unix *)
+          let fd : Unix.file_descr = Obj.magic (fd : int) in
+          [ Lwt_engine.on_readable fd (fun _ -> run_pending ()); timer ]
+        | None -> assert false (* [create_single_threaded] fails instead *))
+      else []
+    in
+    let iter_hooks =
+      if iteration_hooks
+      then (
+        (* The notification is still used for wake-ups from other threads while
+           the loop might be blocked between these hooks *)
+        Stubs.Executor.set_iteration_hooks executor true;
+        Some
+          ( Lwt_main.Enter_iter_hooks.add_first (fun () ->
+              Stubs.Executor.enter_iter executor)
+          , Lwt_main.Leave_iter_hooks.add_first (fun () ->
+              Stubs.Executor.leave_iter executor) ))
+      else None
+    in
+    run_pending ();
+    let t = { executor; notification; reactor_events; iter_hooks } in
     Gc.finalise
-      (fun { executor = _; notification } -> Lwt_unix.stop_notification notification)
+      (fun { executor = _; notification; reactor_events; iter_hooks } ->
+        Lwt_unix.stop_notification notification;
+        List.iter Lwt_engine.stop_event reactor_events;
+        Option.iter
+          (fun (enter, leave) ->
+            Lwt_main.Enter_iter_hooks.remove enter;
+            Lwt_main.Leave_iter_hooks.remove leave)
+          iter_hooks)
       t;
     t
   ;;
 
-  let current () =
+  let check_main_domain () =
     if not (Domain_compat.is_main_domain ())
     then
       failwith
-        "Initializing Rust_async executor from non-main domain is not going to work well";
+        "Initializing Rust_async executor from non-main domain is not going to work well"
+  ;;
+
+  let init
+    ?(single_threaded = false)
+    ?(timer_interval = 0.01)
+    ?(iteration_hooks = false)
+    ()
+    =
+    check_main_domain ();
+    match !current with
+    | Some _ -> failwith "Rust_async.Runtime.init: runtime is already initialized"
+    | None -> current := Some (create ~single_threaded ~timer_interval ~iteration_hooks)
+  ;;
+
+  let current () =
+    check_main_domain ();
     match !current with
     | Some executor -> executor
     | None ->
-      let executor = create () in
+      let executor =
+        create ~single_threaded:false ~timer_interval:0. ~iteration_hooks:false
+      in
       current := Some executor;
       executor
   ;;
+
+  let notifications_sent () = Stubs.Executor.notifications_sent (current ()).executor
+end
+
+module Debug = struct
+  let dump_tasks () = Stubs.Executor.dump_tasks (Runtime.current ()).executor
+
+  let set_task_tracking enabled =
+    Stubs.Executor.set_task_tracking (Runtime.current ()).executor enabled
+  ;;
+end
+
+module Channel = struct
+  type 'a t = Stubs.Channel.t
+
+  let create capacity =
+    if capacity <= 0
+    then invalid_arg "Rust_async.Channel.create: capacity must be positive";
+    Stubs.Channel.create capacity
+  ;;
+
+  let send t v = Stubs.Channel.send t v
+  let recv t = Stubs.Channel.recv t
+  let close t = ignore (Stubs.Channel.close t : bool)
+  let is_closed t = Stubs.Channel.is_closed t
+end
+
+module Io = struct
+  type stream = Stubs.Io.t
+  type reader = Lwt_bytes.t -> int -> int -> int Lwt.t
+
+  type writer =
+    { write : Lwt_bytes.t -> int -> int -> int Lwt.t
+    ; flush : unit -> unit Lwt.t
+    ; close : unit -> unit Lwt.t
+    }
+
+  let channels_of_stream stream =
+    let ic =
+      Lwt_io.make
+        ~close:(fun () -> Lwt.return_unit)
+        ~mode:Lwt_io.input
+        (fun buf ofs len -> Stubs.Io.read stream buf ofs len)
+    in
+    let oc =
+      Lwt_io.make
+        ~close:(fun () -> Stubs.Io.shutdown stream)
+        ~mode:Lwt_io.output
+        (fun buf ofs len -> Stubs.Io.write stream buf ofs len)
+    in
+    ic, oc
+  ;;
+
+  (* Copies data directly from/to the channel buffer, so that the data never
+     goes through OCaml strings *)
+  let reader_of_channel ic buf ofs len =
+    Lwt_io.direct_access ic (fun (da : Lwt_io.direct_access) ->
+      let rec loop () =
+        if da.da_ptr < da.da_max
+        then (
+          let n = min len (da.da_max - da.da_ptr) in
+          Lwt_bytes.blit da.da_buffer da.da_ptr buf ofs n;
+          da.da_ptr <- da.da_ptr + n;
+          Lwt.return n)
+        else
+          Lwt.bind (da.da_perform ()) (fun n -> if n = 0 then Lwt.return 0 else loop ())
+      in
+      loop ())
+  ;;
+
+  let writer_of_channel oc =
+    let write buf ofs len =
+      Lwt_io.direct_access oc (fun (da : Lwt_io.direct_access) ->
+        let rec loop () =
+          if da.da_ptr < da.da_max
+          then (
+            let n = min len (da.da_max - da.da_ptr) in
+            Lwt_bytes.blit buf ofs da.da_buffer da.da_ptr n;
+            da.da_ptr <- da.da_ptr + n;
+            Lwt.return n)
+          else Lwt.bind (da.da_perform ()) (fun _ -> loop ())
+        in
+        loop ())
+    in
+    { write; flush = (fun () -> Lwt_io.flush oc); close = (fun () -> Lwt_io.close oc) }
+  ;;
+end
+
+module Mutex = struct
+  type t = Stubs.Mutex.t
+
+  let create () = Stubs.Mutex.create ()
+  let lock t = Stubs.Mutex.lock t
+
+  let unlock t =
+    if not (Stubs.Mutex.unlock t)
+    then failwith "Rust_async.Mutex.unlock: mutex is not locked from OCaml"
+  ;;
+
+  let is_locked t = Stubs.Mutex.is_locked t
+
+  let with_lock t f =
+    Lwt.bind (lock t) (fun () -> Lwt.finalize f (fun () -> Lwt.return (unlock t)))
+  ;;
+end
+
+module Semaphore = struct
+  type t = Stubs.Semaphore.t
+
+  let max_permits = Stubs.Semaphore.max_permits ()
+
+  (* Tokio panics on overflowing permits, which would abort the process *)
+  let check_permits name n =
+    if n < 0 || n > max_permits
+    then
+      invalid_arg
+        (Printf.sprintf
+           "Rust_async.Semaphore.%s: number of permits must be within 0..%d"
+           name
+           max_permits)
+  ;;
+
+  let create permits =
+    check_permits "create" permits;
+    match Stubs.Semaphore.create permits with
+    | Ok t -> t
+    | Error msg -> invalid_arg ("Rust_async.Semaphore.create: " ^ msg)
+  ;;
+
+  let acquire ?(n = 1) t =
+    check_permits "acquire" n;
+    Stubs.Semaphore.acquire t n
+  ;;
+
+  let release ?(n = 1) t =
+    check_permits "release" n;
+    match Stubs.Semaphore.release t n with
+    | Ok () -> ()
+    | Error msg -> invalid_arg ("Rust_async.Semaphore.release: " ^ msg)
+  ;;
+
+  let available t = Stubs.Semaphore.available t
+
+  let with_permit ?(n = 1) t f =
+    Lwt.bind (acquire ~n t) (fun () ->
+      Lwt.finalize f (fun () -> Lwt.return (release ~n t)))
+  ;;
+end
+
+module Notify = struct
+  type t = Stubs.Notify.t
+
+  let create () = Stubs.Notify.create ()
+  let wait t = Stubs.Notify.wait t
+  let notify_one t = Stubs.Notify.notify_one t
+  let notify_waiters t = Stubs.Notify.notify_waiters t
+end
+
+module Signal = struct
+  type t = Stubs.Signal.t
+
+  let listen signo =
+    match Stubs.Signal.listen signo with
+    | Ok t -> t
+    | Error msg -> failwith ("Rust_async.Signal.listen: " ^ msg)
+  ;;
+
+  let next t = Stubs.Signal.next t
+  let wait signo = next (listen signo)
+
+  let stream signo =
+    let t = listen signo in
+    Lwt_stream.from (fun () -> Lwt.map Option.some (next t))
+  ;;
+end
+
+module Closure = struct
+  type 'f fn = 'f
 end
 
+(* Slots of the root table of ../src/root_table.rs, Rust keeps its own handle
+   to the same array *)
+let root_table : Obj.t array ref = ref [||]
+
 let () =
-  (* Below callbacks are used in ../src/promise.rs and ../src/domain_executor.rs *)
+  (* Below callbacks are used in ../src/promise.rs, ../src/domain_executor.rs,
+     ../src/lwt_bytes.rs, ../src/async_func.rs and ../src/time.rs *)
   Callback.register "olwti_lwt_task" Lwt.task;
-  Callback.register "olwti_lwt_wakeup_later" (fun resolver v ->
-    try Ok (Lwt.wakeup_later resolver v) with
-    | e -> Error ("Lwt.wakup_later failed: " ^ Printexc.to_string e));
-  Callback.register "olwti_lwt_wakeup_later_exn" (fun resolver msg ->
-    try Ok (Lwt.wakeup_later_exn resolver (Failure msg)) with
-    | e -> Error ("Lwt.wakup_later_exn failed: " ^ Printexc.to_string e));
+  Callback.register "olwti_root_table_grow" (fun len ->
+    let slots = Array.make len (Obj.repr ()) in
+    (* The table might have been grown meanwhile, e.g. by a finaliser *)
+    let old = !root_table in
+    if Array.length old >= len
+    then old, Array.length old
+    else (
+      Array.blit old 0 slots 0 (Array.length old);
+      root_table := slots;
+      slots, len));
+  Callback.register "olwti_lwt_return" Lwt.return;
+  Callback.register "olwti_lwt_fail_overloaded" (fun () -> Lwt.fail Overloaded);
+  Callback.register "olwti_lwt_fail_with" Lwt.fail_with;
+  Callback.register "olwti_lwt_fail" Lwt.fail;
+  Callback.register "olwti_lwt_sleep" Lwt_unix.sleep;
+  Callback.register "olwti_lwt_pause" Lwt.pause;
+  Callback.register "olwti_lwt_bytes_create" Lwt_bytes.create;
+  Callback.register "olwti_lwt_bytes_proxy" Lwt_bytes.proxy;
+  Callback.register "olwti_lwt_wakeup_batch" (fun batch ->
+    (* [batch] holds [resolver; kind; payload] triples, where resolver and
+       payload are slots of [root_table], and kinds are [SETTLE_*] constants in
+       src/promise.rs. Callbacks of settled promises may grow the table, so it's
+       looked up for each settlement *)
+    for i = 0 to (Array.length batch / 3) - 1 do
+      let resolver = Obj.obj !root_table.(batch.(3 * i)) in
+      let payload = !root_table.(batch.((3 * i) + 2)) in
+      try
+        match batch.((3 * i) + 1) with
+        | 0 -> Lwt.wakeup_later resolver payload
+        | 1 -> Lwt.wakeup_later_exn resolver (Failure (Obj.obj payload))
+        | _ -> Lwt.wakeup_later_exn resolver (Obj.obj payload)
+      with
+      | e -> !Lwt.async_exception_hook e
+    done);
   Callback.register "olwti_current_executor" (fun () ->
     let current = Runtime.current () in
     current.executor);
+  Callback.register "olwti_rust_closure" (fun closure arity ->
+    (* Arguments are packed into a block rather than an array, as an array of
+       [Obj.t] might turn out to be a float array *)
+    let call args =
+      let block = Obj.new_block 0 (max 1 (List.length args)) in
+      List.iteri (Obj.set_field block) args;
+      Stubs.Closure.call closure block
+    in
+    match arity with
+    | 0 -> Obj.repr (fun () -> call [])
+    | 1 -> Obj.repr (fun a -> call [ Obj.repr a ])
+    | 2 -> Obj.repr (fun a b -> call [ Obj.repr a; Obj.repr b ])
+    | 3 -> Obj.repr (fun a b c -> call [ Obj.repr a; Obj.repr b; Obj.repr c ])
+    | 4 ->
+      Obj.repr (fun a b c d -> call [ Obj.repr a; Obj.repr b; Obj.repr c; Obj.repr d ])
+    | n -> invalid_arg (Printf.sprintf "Rust closure of arity %d is not supported" n));
+  Callback.register "olwti_lwt_join" (fun promises -> Lwt.join (Array.to_list promises));
+  Callback.register "olwti_lwt_pick" (fun promises -> Lwt.pick (Array.to_list promises));
+  Callback.register "olwti_lwt_choose" (fun promises ->
+    Lwt.choose (Array.to_list promises));
+  Callback.register "olwti_lwt_all" (fun promises ->
+    Lwt.map Array.of_list (Lwt.all (Array.to_list promises)));
+  Callback.register "olwti_lwt_both" Lwt.both;
+  Callback.register "olwti_lwt_peek" (fun fut ->
+    match Lwt.state fut with
+    | Lwt.Return value -> Some (Ok value)
+    | Lwt.Fail exn -> Some (Error (Printexc.to_string exn))
+    | Lwt.Sleep -> None);
   Callback.register "olwti_wrap_lwt_future" (fun fut ->
     let wrapper = Stubs.Future.create () in
     Lwt.on_any
@@ -1 +1,208 @@
-(* Deliberately left empty *)
+(** Rust executor, running async Rust tasks on the main domain, i.e.
+    [ocaml_lwt_interop::domain_executor::DomainExecutor] on Rust side. It is
+    created on first use, unless it's initialized explicitly with [init]. *)
+module Runtime : sig
+  (** [init ?single_threaded ?timer_interval ()] initializes the executor. Raises
+      [Failure] if the executor is already initialized, i.e. [init] must be
+      called before any Rust stubs are used.
+
+      By default, Rust I/O is performed by the global multi-threaded Tokio
+      runtime. With [~single_threaded:true], the executor gets its own
+      [current_thread] Tokio runtime, which is turned by the Lwt event loop
+      whenever its I/O driver has pending events, so that Rust sockets and OCaml
+      sockets share one thread. Tokio timers are only checked every
+      [timer_interval] seconds (10ms by default) in this mode, so Rust timers
+      may fire up to that late, and the process wakes up that often even when
+      idle, i.e. 100 times per second by default; a smaller interval improves
+      timer precision at the cost of CPU. Raises [Failure] if the I/O driver
+      of the runtime can not be registered in the Lwt event loop (it's only
+      supported on Linux).
+
+      By default, each wake-up of a Rust task sends a notification to Lwt event
+      loop, i.e. writes to a pipe. With [~iteration_hooks:true], the executor is
+      run from [Lwt_main.Enter_iter_hooks] and [Lwt_main.Leave_iter_hooks]
+      instead, and the notification is only sent while the event loop might be
+      blocked waiting for I/O, which is cheaper when Rust and Lwt tasks
+      frequently wake each other up. *)
+  val init
+    :  ?single_threaded:bool
+    -> ?timer_interval:float
+    -> ?iteration_hooks:bool
+    -> unit
+    -> unit
+
+  (** Number of notifications sent to Lwt event loop by the executor so far. *)
+  val notifications_sent : unit -> int
+end
+
+(** Introspection of the executor, e.g. for finding Rust tasks behind promises
+    which never resolve. *)
+module Debug : sig
+  (** [dump_tasks ()] describes Rust tasks, which are spawned onto the executor
+      and are not completed yet, oldest first, one task per line: the name of
+      the task (the stub function name for tasks spawned by async stubs), the
+      location where it has been spawned, its age, and the number of times it
+      has been polled. See [ocaml_lwt_interop::tasks]. Only tasks spawned
+      while tracking is enabled are listed. *)
+  val dump_tasks : unit -> string
+
+  (** [set_task_tracking enabled] switches tracking of newly spawned tasks on
+      or off. Tracking is disabled by default, as it costs a lock and an
+      allocation per spawned task. *)
+  val set_task_tracking : bool -> unit
+end
+
+(** Promises returned by async Rust stubs with a concurrency limit in
+    [overload = "reject"] mode are rejected with [Overloaded], when the limit is
+    reached, see [ocaml_lwt_interop::concurrency]. *)
+exception Overloaded
+
+(** Bounded channels which can be shared between Lwt and Rust tasks.
+
+    A channel is backed by a Rust channel, so the same ['a t] can be passed to
+    Rust stubs and used from Rust tasks as well. *)
+module Channel : sig
+  type 'a t
+
+  (** [create capacity] creates a channel, which buffers up to [capacity] values.
+      Raises [Invalid_argument] if [capacity] is not positive. *)
+  val create : int -> 'a t
+
+  (** [send t v] sends [v] into the channel, the promise is pending while the
+      channel is full. The promise is rejected if the channel is closed. *)
+  val send : 'a t -> 'a -> unit Lwt.t
+
+  (** [recv t] receives a value from the channel, the promise is pending while
+      the channel is empty. Resolves to [None] once the channel is closed and
+      all buffered values have been received. *)
+  val recv : 'a t -> 'a option Lwt.t
+
+  (** [close t] closes the channel for both senders and receivers. *)
+  val close : 'a t -> unit
+
+  val is_closed : 'a t -> bool
+end
+
+(** Bridging between Rust [AsyncRead]/[AsyncWrite] and [Lwt_io] channels. Data is
+    copied through [Lwt_bytes.t] buffers. *)
+module Io : sig
+  (** Rust stream, i.e. [ocaml_lwt_interop::lwt_io::Stream] on Rust side. *)
+  type stream
+
+  (** Reader, which Rust can use as [AsyncRead], i.e.
+      [ocaml_lwt_interop::lwt_io::LwtReader] on Rust side. *)
+  type reader
+
+  (** Writer, which Rust can use as [AsyncWrite], i.e.
+      [ocaml_lwt_interop::lwt_io::LwtWriter] on Rust side. *)
+  type writer
+
+  (** [channels_of_stream stream] creates a pair of channels, reading from and
+      writing to Rust [stream]. Closing the output channel shuts down the write
+      half of the stream. *)
+  val channels_of_stream : stream -> Lwt_io.input_channel * Lwt_io.output_channel
+
+  val reader_of_channel : Lwt_io.input_channel -> reader
+  val writer_of_channel : Lwt_io.output_channel -> writer
+end
+
+(** Async mutex, backed by Rust [tokio::sync::Mutex]. The same mutex can be
+    locked from OCaml and from Rust ([ocaml_lwt_interop::sync::Mutex]), waiters
+    are served in FIFO order regardless of the side they come from. *)
+module Mutex : sig
+  type t
+
+  val create : unit -> t
+
+  (** [lock t] resolves once the mutex is locked on behalf of OCaml. *)
+  val lock : t -> unit Lwt.t
+
+  (** [unlock t] unlocks the mutex, locked via [lock]. Raises [Failure] if the
+      mutex is not locked from OCaml. *)
+  val unlock : t -> unit
+
+  val is_locked : t -> bool
+
+  (** [with_lock t f] runs [f] with the mutex locked, and unlocks it once the
+      promise returned by [f] is resolved or rejected. *)
+  val with_lock : t -> (unit -> 'a Lwt.t) -> 'a Lwt.t
+end
+
+(** Async counting semaphore, backed by Rust [tokio::sync::Semaphore], see
+    [ocaml_lwt_interop::sync::Semaphore]. *)
+module Semaphore : sig
+  type t
+
+  (** The maximum number of permits a semaphore can hold. Functions below raise
+      [Invalid_argument] if the number of permits is negative or exceeds
+      [max_permits]. *)
+  val max_permits : int
+
+  (** [create permits] creates a semaphore with [permits] permits available. *)
+  val create : int -> t
+
+  (** [acquire ?n t] resolves once [n] permits (1 by default) are acquired. *)
+  val acquire : ?n:int -> t -> unit Lwt.t
+
+  (** [release ?n t] releases [n] permits (1 by default) back to the semaphore.
+      Raises [Invalid_argument] if the semaphore would hold more than
+      [max_permits] permits. *)
+  val release : ?n:int -> t -> unit
+
+  val available : t -> int
+  val with_permit : ?n:int -> t -> (unit -> 'a Lwt.t) -> 'a Lwt.t
+end
+
+(** Notification primitive, backed by Rust [tokio::sync::Notify], see
+    [ocaml_lwt_interop::sync::Notify]. *)
+module Notify : sig
+  type t
+
+  val create : unit -> t
+
+  (** [wait t] resolves once notified. *)
+  val wait : t -> unit Lwt.t
+
+  (** [notify_one t] wakes up a single waiter, or stores a permit for the next
+      one if there are no waiters. *)
+  val notify_one : t -> unit
+
+  (** [notify_waiters t] wakes up all current waiters. *)
+  val notify_waiters : t -> unit
+end
+
+(** Unix signals, received by Rust [tokio::signal::unix] on the shared Tokio
+    runtime and forwarded to Lwt, see [ocaml_lwt_interop::signal]. Unlike
+    [Lwt_unix.on_signal], signals are observed regardless of which thread the
+    kernel has delivered them to.
+
+    Signal numbers are OCaml ones, such as [Sys.sigterm]. Once a signal is
+    listened to, its default action is disabled for the rest of the process
+    lifetime. *)
+module Signal : sig
+  type t
+
+  (** [listen signo] starts listening for signal [signo]. Raises [Failure] if
+      the signal can not be listened to (e.g. [Sys.sigkill]). *)
+  val listen : int -> t
+
+  (** [next t] resolves once the signal is received. Signals received since the
+      previous [next] are coalesced into one. *)
+  val next : t -> unit Lwt.t
+
+  (** [wait signo] resolves once signal [signo] is received. *)
+  val wait : int -> unit Lwt.t
+
+  (** [stream signo] is a stream, which yields a value each time signal [signo]
+      is received. *)
+  val stream : int -> unit Lwt_stream.t
+end
+
+(** Async closures, implemented in Rust, see
+    [ocaml_lwt_interop::async_func::RustAsyncFunc]. *)
+module Closure : sig
+  (** Stubs returning Rust closures are declared with result type
+      [(int64 -> int64 Lwt.t) fn] rather than [(int64 -> int64 Lwt.t)], as
+      the latter would change the arity of the external. *)
+  type 'f fn = 'f
+end
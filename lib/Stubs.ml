@@ -24,5 +24,132 @@ module Executor = struct
   type t = tags t'
 
   external create : int -> _ t' = "lwti_executor_create"
+
+  external create_single_threaded
+    :  int
+    -> (_ t', string) result
+    = "lwti_executor_create_single_threaded"
+
+  external reactor_fd : _ t' -> int option = "lwti_executor_reactor_fd"
   external run_pending : _ t' -> unit = "lwti_executor_run_pending"
+
+  external set_iteration_hooks
+    :  _ t'
+    -> bool
+    -> unit
+    = "lwti_executor_set_iteration_hooks"
+
+  external enter_iter : _ t' -> unit = "lwti_executor_enter_iter"
+  external leave_iter : _ t' -> unit = "lwti_executor_leave_iter"
+  external notifications_sent : _ t' -> int = "lwti_executor_notifications_sent"
+  external dump_tasks : _ t' -> string = "lwti_executor_dump_tasks"
+  external set_task_tracking : _ t' -> bool -> unit = "lwti_executor_set_task_tracking"
+end
+
+module Closure = struct
+  type tags =
+    [ `Ocaml_lwt_interop_async_func_rust_closure
+    | `Core_marker_sync
+    | `Core_marker_send
+    ]
+
+  type 'a t' = ([> tags ] as 'a) Ocaml_rs_smartptr.Rusty_obj.t
+  type t = tags t'
+
+  external call : _ t' -> 'a -> 'b = "lwti_closure_call"
+end
+
+module Channel = struct
+  type tags =
+    [ `Ocaml_lwt_interop_channel_ml_channel
+    | `Core_marker_sync
+    | `Core_marker_send
+    ]
+
+  type 'a t' = ([> tags ] as 'a) Ocaml_rs_smartptr.Rusty_obj.t
+  type t = tags t'
+
+  external create : int -> _ t' = "lwti_channel_create"
+  external send : _ t' -> 'a -> unit Lwt.t = "lwti_channel_send"
+  external recv : _ t' -> 'a option Lwt.t = "lwti_channel_recv"
+  external close : _ t' -> bool = "lwti_channel_close"
+  external is_closed : _ t' -> bool = "lwti_channel_is_closed"
+end
+
+module Io = struct
+  type tags =
+    [ `Ocaml_lwt_interop_lwt_io_io_stream
+    | `Core_marker_sync
+    | `Core_marker_send
+    ]
+
+  type 'a t' = ([> tags ] as 'a) Ocaml_rs_smartptr.Rusty_obj.t
+  type t = tags t'
+
+  external read : _ t' -> Lwt_bytes.t -> int -> int -> int Lwt.t = "lwti_io_read"
+  external write : _ t' -> Lwt_bytes.t -> int -> int -> int Lwt.t = "lwti_io_write"
+  external shutdown : _ t' -> unit Lwt.t = "lwti_io_shutdown"
+end
+
+module Mutex = struct
+  type tags =
+    [ `Ocaml_lwt_interop_sync_mutex
+    | `Core_marker_sync
+    | `Core_marker_send
+    ]
+
+  type 'a t' = ([> tags ] as 'a) Ocaml_rs_smartptr.Rusty_obj.t
+  type t = tags t'
+
+  external create : unit -> _ t' = "lwti_mutex_create"
+  external lock : _ t' -> unit Lwt.t = "lwti_mutex_lock"
+  external unlock : _ t' -> bool = "lwti_mutex_unlock"
+  external is_locked : _ t' -> bool = "lwti_mutex_is_locked"
+end
+
+module Semaphore = struct
+  type tags =
+    [ `Ocaml_lwt_interop_sync_semaphore
+    | `Core_marker_sync
+    | `Core_marker_send
+    ]
+
+  type 'a t' = ([> tags ] as 'a) Ocaml_rs_smartptr.Rusty_obj.t
+  type t = tags t'
+
+  external max_permits : unit -> int = "lwti_semaphore_max_permits"
+  external create : int -> (_ t', string) result = "lwti_semaphore_create"
+  external acquire : _ t' -> int -> unit Lwt.t = "lwti_semaphore_acquire"
+  external release : _ t' -> int -> (unit, string) result = "lwti_semaphore_release"
+  external available : _ t' -> int = "lwti_semaphore_available"
+end
+
+module Notify = struct
+  type tags =
+    [ `Ocaml_lwt_interop_sync_notify
+    | `Core_marker_sync
+    | `Core_marker_send
+    ]
+
+  type 'a t' = ([> tags ] as 'a) Ocaml_rs_smartptr.Rusty_obj.t
+  type t = tags t'
+
+  external create : unit -> _ t' = "lwti_notify_create"
+  external wait : _ t' -> unit Lwt.t = "lwti_notify_wait"
+  external notify_one : _ t' -> unit = "lwti_notify_notify_one"
+  external notify_waiters : _ t' -> unit = "lwti_notify_notify_waiters"
+end
+
+module Signal = struct
+  type tags =
+    [ `Ocaml_lwt_interop_signal_signal_listener
+    | `Core_marker_sync
+    | `Core_marker_send
+    ]
+
+  type 'a t' = ([> tags ] as 'a) Ocaml_rs_smartptr.Rusty_obj.t
+  type t = tags t'
+
+  external listen : int -> (_ t', string) result = "lwti_signal_listen"
+  external next : _ t' -> unit Lwt.t = "lwti_signal_next"
 end
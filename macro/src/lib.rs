@@ -1,12 +1,217 @@
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
-use quote::quote;
-use syn::{parse_macro_input, ItemFn};
+use quote::{quote, quote_spanned, ToTokens};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, AttributeArgs, ItemFn, ItemImpl, ItemTrait};
 
 #[proc_macro_attribute]
-pub fn func(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn func(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let attr = parse_macro_input!(attr as AttributeArgs);
     let input = parse_macro_input!(item as ItemFn);
-    func_impl(input).into()
+    FuncArgs::parse(attr)
+        .and_then(|args| func_impl(args, input))
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+#[proc_macro_attribute]
+pub fn methods(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let attr = parse_macro_input!(attr as AttributeArgs);
+    let input = parse_macro_input!(item as ItemImpl);
+    MethodsArgs::parse(attr)
+        .and_then(|args| methods_impl(args, input))
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+#[proc_macro_attribute]
+pub fn ocaml_trait(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let attr = parse_macro_input!(attr as AttributeArgs);
+    let input = parse_macro_input!(item as ItemTrait);
+    OCamlTraitArgs::parse(attr)
+        .and_then(|args| ocaml_trait_impl(args, input))
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+#[proc_macro_attribute]
+pub fn blocking(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let attr = parse_macro_input!(attr as AttributeArgs);
+    let input = parse_macro_input!(item as ItemFn);
+    if let Some(arg) = attr.first() {
+        return syn::Error::new_spanned(arg, "`blocking` does not take any arguments")
+            .to_compile_error()
+            .into();
+    }
+    blocking_impl(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// How `Err` values of `Result`-returning functions are passed to OCaml
+#[derive(Debug, Default, PartialEq)]
+enum ErrMode {
+    /// Promise is resolved with `('a, 'e) result`, i.e. `Lwt_result.t`
+    #[default]
+    Result,
+    /// Promise is rejected with `Failure`, carrying `Display` of `Err` value
+    Raise,
+    /// Promise is rejected with `Err` value, which converts to OCaml exception
+    Exn,
+}
+
+/// Where the body of the function is executed
+#[derive(Debug, Default, PartialEq)]
+enum RunMode {
+    /// Body is executed on OCaml domain executor
+    #[default]
+    Domain,
+    /// Body is executed on Tokio runtime via `spawn`
+    Tokio,
+    /// Body is executed on Tokio blocking thread pool via `spawn_blocking`
+    Blocking,
+}
+
+/// What happens to calls over the concurrency limit
+#[derive(Debug, Default, PartialEq)]
+enum OverloadMode {
+    /// Calls wait for a permit in FIFO order
+    #[default]
+    Wait,
+    /// Calls are rejected with `Rust_async.Overloaded` right away
+    Reject,
+}
+
+/// Arguments of `#[ocaml_lwt_interop::func(...)]` attribute
+#[derive(Debug, Default)]
+struct FuncArgs {
+    err: ErrMode,
+    mode: RunMode,
+    /// Maximum number of concurrently running bodies, unlimited by default
+    max_concurrent: Option<usize>,
+    overload: OverloadMode,
+}
+
+/// Maximum number of permits of `tokio::sync::Semaphore`, which backs
+/// `max_concurrent` limit
+const MAX_PERMITS: usize = usize::MAX >> 3;
+
+impl FuncArgs {
+    fn parse(args: AttributeArgs) -> syn::Result<Self> {
+        let mut res = FuncArgs::default();
+        let mut overload_lit = None;
+        for arg in args {
+            match arg {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(nv))
+                    if nv.path.is_ident("max_concurrent") =>
+                {
+                    res.max_concurrent = match &nv.lit {
+                        syn::Lit::Int(int) => match int.base10_parse::<usize>() {
+                            Ok(n) if n > MAX_PERMITS => {
+                                return Err(syn::Error::new_spanned(
+                                    int,
+                                    format!("`max_concurrent` can not exceed {}", MAX_PERMITS),
+                                ))
+                            }
+                            Ok(n) if n > 0 => Some(n),
+                            _ => {
+                                return Err(syn::Error::new_spanned(
+                                    int,
+                                    "expected a positive integer",
+                                ))
+                            }
+                        },
+                        lit => {
+                            return Err(syn::Error::new_spanned(
+                                lit,
+                                "expected a positive integer",
+                            ))
+                        }
+                    };
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(nv))
+                    if nv.path.is_ident("overload") =>
+                {
+                    res.overload = match &nv.lit {
+                        syn::Lit::Str(s) if s.value() == "wait" => OverloadMode::Wait,
+                        syn::Lit::Str(s) if s.value() == "reject" => OverloadMode::Reject,
+                        lit => {
+                            return Err(syn::Error::new_spanned(
+                                lit,
+                                "expected one of \"wait\" or \"reject\"",
+                            ))
+                        }
+                    };
+                    overload_lit = Some(nv.lit);
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(nv))
+                    if nv.path.is_ident("err") =>
+                {
+                    res.err = match &nv.lit {
+                        syn::Lit::Str(s) if s.value() == "result" => ErrMode::Result,
+                        syn::Lit::Str(s) if s.value() == "raise" => ErrMode::Raise,
+                        syn::Lit::Str(s) if s.value() == "exn" => ErrMode::Exn,
+                        lit => {
+                            return Err(syn::Error::new_spanned(
+                                lit,
+                                "expected one of \"result\", \"raise\" or \"exn\"",
+                            ))
+                        }
+                    }
+                }
+                syn::NestedMeta::Meta(syn::Meta::Path(path))
+                    if path.is_ident("tokio") || path.is_ident("blocking") =>
+                {
+                    if res.mode != RunMode::Domain {
+                        return Err(syn::Error::new_spanned(
+                            path,
+                            "`tokio` and `blocking` are mutually exclusive",
+                        ));
+                    }
+                    res.mode = if path.is_ident("tokio") {
+                        RunMode::Tokio
+                    } else {
+                        RunMode::Blocking
+                    };
+                }
+                arg => {
+                    return Err(syn::Error::new_spanned(
+                        arg,
+                        "unsupported argument, expected `err = \"...\"`, `tokio`, `blocking`, `max_concurrent = N` or `overload = \"...\"`",
+                    ))
+                }
+            }
+        }
+        if let (Some(lit), None) = (overload_lit, &res.max_concurrent) {
+            return Err(syn::Error::new_spanned(
+                lit,
+                "`overload` requires `max_concurrent` argument",
+            ));
+        }
+        Ok(res)
+    }
+}
+
+/// Extracts `T` out of `Result<T, E>`
+fn result_ok_type(typ: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(type_path) = typ else {
+        return None;
+    };
+    let segment = type_path.path.segments.last()?;
+    if segment.ident != "Result" {
+        return None;
+    }
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    let mut types = args.args.iter().filter_map(|arg| match arg {
+        syn::GenericArgument::Type(typ) => Some(typ),
+        _ => None,
+    });
+    match (types.next(), types.next(), types.next()) {
+        (Some(ok), Some(_), None) => Some(ok),
+        _ => None,
+    }
 }
 
 fn paths_equal(path1: &syn::Path, path2: &syn::Path) -> bool {
@@ -32,29 +237,149 @@ fn segments_equal(seg1: &syn::PathSegment, seg2: &syn::PathSegment) -> bool {
 
     match (&seg1.arguments, &seg2.arguments) {
         (syn::PathArguments::None, syn::PathArguments::None) => true,
+        // Generic arguments are compared by their tokens
         (
-            syn::PathArguments::AngleBracketed(_),
-            syn::PathArguments::AngleBracketed(_),
+            syn::PathArguments::AngleBracketed(args1),
+            syn::PathArguments::AngleBracketed(args2),
         ) => {
-            unimplemented!("Path equality with angle bracket args is not implemented")
-        }
-        (syn::PathArguments::Parenthesized(_), syn::PathArguments::Parenthesized(_)) => {
-            unimplemented!("Path equality with parenthesized args is not implemented")
+            args1.args.to_token_stream().to_string()
+                == args2.args.to_token_stream().to_string()
         }
+        (
+            syn::PathArguments::Parenthesized(args1),
+            syn::PathArguments::Parenthesized(args2),
+        ) => args1.to_token_stream().to_string() == args2.to_token_stream().to_string(),
         _ => false,
     }
 }
 
-fn func_impl(input: ItemFn) -> TokenStream2 {
+/// Finds calls of `ocaml_runtime` in `tokens`, which would panic in bodies
+/// running outside of OCaml domain executor.
+///
+/// This is a best-effort lint, catching the obvious mistake early: only
+/// direct calls written in the body itself are found, calls made by other
+/// functions or macros are not, and an unrelated function named
+/// `ocaml_runtime` is rejected too. The actual guarantee is the runtime check
+/// in `ocaml_runtime` itself.
+fn find_ocaml_runtime(tokens: TokenStream2) -> Option<proc_macro2::Ident> {
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(tt) = tokens.next() {
+        match tt {
+            proc_macro2::TokenTree::Ident(ident) if ident == "ocaml_runtime" => {
+                if let Some(proc_macro2::TokenTree::Group(group)) = tokens.peek() {
+                    if group.delimiter() == proc_macro2::Delimiter::Parenthesis {
+                        return Some(ident);
+                    }
+                }
+            }
+            proc_macro2::TokenTree::Group(group) => {
+                if let Some(ident) = find_ocaml_runtime(group.stream()) {
+                    return Some(ident);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Rejects argument types, which can not be moved into the spawned task
+fn check_arg_type(ty: &syn::Type) -> syn::Result<()> {
+    match ty {
+        syn::Type::Reference(_) => Err(syn::Error::new_spanned(
+            ty,
+            "arguments of async stubs can not be references, as they must outlive the stub call, use owned types instead",
+        )),
+        syn::Type::ImplTrait(_) => Err(syn::Error::new_spanned(
+            ty,
+            "arguments of async stubs can not be `impl Trait`, use concrete types instead",
+        )),
+        syn::Type::Group(group) => check_arg_type(&group.elem),
+        syn::Type::Paren(paren) => check_arg_type(&paren.elem),
+        _ => Ok(()),
+    }
+}
+
+/// Separates `#[ocaml::func]` from other attributes, creates a default one if
+/// it's not present
+fn split_ocaml_func_attr(
+    attrs: Vec<syn::Attribute>,
+) -> (TokenStream2, Vec<syn::Attribute>) {
+    let expected_path = syn::parse2::<syn::Path>(quote! {ocaml::func}).unwrap();
+    let (ocaml_func_attr, other_attrs): (Vec<_>, Vec<_>) = attrs
+        .into_iter()
+        .partition(|attr| paths_equal(&attr.path, &expected_path));
+
+    // Use the existing #[ocaml::func] if present, otherwise create a default one
+    let ocaml_func_attr = if !ocaml_func_attr.is_empty() {
+        quote! { #(#ocaml_func_attr)* }
+    } else {
+        quote! { #[ocaml::func] }
+    };
+    (ocaml_func_attr, other_attrs)
+}
+
+/// Returns identifiers and types of stub arguments. Outer function receives
+/// arguments under plain identifiers, which are passed to the inner function,
+/// where original patterns are matched.
+fn stub_args<'a>(
+    fn_args: &'a syn::punctuated::Punctuated<syn::FnArg, syn::token::Comma>,
+    receiver_msg: &str,
+) -> syn::Result<Vec<(syn::Ident, &'a syn::Type)>> {
+    fn_args
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| {
+            let pat_type = match arg {
+                syn::FnArg::Typed(pat_type) => pat_type,
+                syn::FnArg::Receiver(receiver) => {
+                    return Err(syn::Error::new_spanned(receiver, receiver_msg))
+                }
+            };
+            check_arg_type(&pat_type.ty)?;
+            let ident = match pat_type.pat.as_ref() {
+                syn::Pat::Ident(pat_ident)
+                    if pat_ident.by_ref.is_none() && pat_ident.subpat.is_none() =>
+                {
+                    pat_ident.ident.clone()
+                }
+                _ => syn::Ident::new(
+                    &format!("__olwti_arg{}", i),
+                    proc_macro2::Span::call_site(),
+                ),
+            };
+            Ok((ident, pat_type.ty.as_ref()))
+        })
+        .collect()
+}
+
+fn func_impl(args: FuncArgs, input: ItemFn) -> syn::Result<TokenStream2> {
     let fn_name = &input.sig.ident;
     let fn_body_stmts = &input.block.stmts;
     let fn_args = &input.sig.inputs;
     let fn_ret = match &input.sig.output {
+        syn::ReturnType::Default if args.err != ErrMode::Result => {
+            return Err(syn::Error::new_spanned(
+                &input.sig,
+                "`err` argument requires function to return `Result<T, E>`",
+            ))
+        }
         syn::ReturnType::Default => syn::parse2::<syn::ReturnType>(
             quote! { -> ::ocaml_lwt_interop::promise::Promise<()> },
         )
         .unwrap(),
         syn::ReturnType::Type(rarrow, typ) => {
+            let typ = match args.err {
+                ErrMode::Result => typ.as_ref(),
+                ErrMode::Raise | ErrMode::Exn => {
+                    result_ok_type(typ).ok_or_else(|| {
+                        syn::Error::new_spanned(
+                            typ,
+                            "`err` argument requires function to return `Result<T, E>`",
+                        )
+                    })?
+                }
+            };
             let new_typ = syn::parse2::<syn::Type>(
                 quote! { ::ocaml_lwt_interop::promise::Promise<#typ> },
             )
@@ -62,35 +387,59 @@ fn func_impl(input: ItemFn) -> TokenStream2 {
             syn::ReturnType::Type(*rarrow, Box::new(new_typ))
         }
     };
+    let settle = match args.err {
+        ErrMode::Result => quote! { resolver.resolve(gc, &res); },
+        ErrMode::Raise => quote! { resolver.settle(gc, &res); },
+        ErrMode::Exn => quote! { resolver.settle_exn(gc, &res); },
+    };
+    let settled = match args.err {
+        ErrMode::Result => {
+            quote! { ::ocaml_lwt_interop::promise::Promise::resolved(gc, &res) }
+        }
+        ErrMode::Raise => {
+            quote! { ::ocaml_lwt_interop::promise::Promise::settled(gc, &res) }
+        }
+        ErrMode::Exn => {
+            quote! { ::ocaml_lwt_interop::promise::Promise::settled_exn(gc, &res) }
+        }
+    };
 
-    let expected_path = syn::parse2::<syn::Path>(quote! {ocaml::func}).unwrap();
-    // Separate #[ocaml::func] from other attributes
-    let (ocaml_func_attr, other_attrs): (Vec<_>, Vec<_>) = input
-        .attrs
-        .into_iter()
-        .partition(|attr| paths_equal(&attr.path, &expected_path));
+    let (ocaml_func_attr, other_attrs) = split_ocaml_func_attr(input.attrs);
 
-    // Use the existing #[ocaml::func] if present, otherwise create a default one
-    let ocaml_func_attr = if !ocaml_func_attr.is_empty() {
-        quote! { #(#ocaml_func_attr)* }
+    let args_info = stub_args(
+        fn_args,
+        "`func` can not be used on methods, use `#[ocaml_lwt_interop::methods]` on the impl block instead",
+    )?;
+    let outer_args: Vec<_> = args_info
+        .iter()
+        .map(|(ident, ty)| quote! { #ident: #ty })
+        .collect();
+    let call_args: Vec<_> = args_info.iter().map(|(ident, _)| ident).collect();
+    // Arguments are moved into the spawned task, so they must be `Send` and
+    // `'static`, assertions point at the offending argument type
+    let arg_asserts = if args_info.is_empty() {
+        quote! {}
     } else {
-        quote! { #[ocaml::func] }
+        let asserts = args_info.iter().map(|(_, ty)| {
+            quote_spanned! {ty.span()=>
+                __olwti_assert_send_static::<#ty>();
+            }
+        });
+        quote! {
+            fn __olwti_assert_send_static<T: Send + 'static>() {}
+            #(#asserts)*
+        }
     };
 
-    // Prepare arguments for calling the inner function. We expect patterns to be
-    // simple identifiers.
-    let call_args: Vec<syn::Ident> = fn_args
-        .iter()
-        .filter_map(|arg| match arg {
-            syn::FnArg::Typed(pat_type) => match pat_type.pat.as_ref() {
-                syn::Pat::Ident(pat_ident) => Some(pat_ident.ident.clone()),
-                _ => None,
-            },
-            _ => None,
-        })
-        .collect();
-    let inner_fn_name = syn::Ident::new("__inner", proc_macro2::Span::call_site());
     let fn_generics = &input.sig.generics;
+    if let Some(param) = fn_generics.params.first() {
+        return Err(syn::Error::new_spanned(
+            param,
+            "async stubs can not have generic or lifetime parameters",
+        ));
+    }
+    let where_clause = &fn_generics.where_clause;
+    let inner_fn_name = syn::Ident::new("__inner", proc_macro2::Span::call_site());
     let fn_output = match &input.sig.output {
         syn::ReturnType::Default => {
             syn::parse2::<syn::ReturnType>(quote! { -> () }).unwrap()
@@ -98,25 +447,546 @@ fn func_impl(input: ItemFn) -> TokenStream2 {
         other => other.clone(),
     };
 
-    quote! {
+    if args.mode != RunMode::Domain {
+        if let Some(ident) = find_ocaml_runtime(input.block.to_token_stream()) {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "`ocaml_runtime` can not be used in `tokio` or `blocking` mode, as the body does not run on OCaml domain executor",
+            ));
+        }
+    }
+    // Only argument conversion and promise resolution happen on OCaml domain
+    // executor in `tokio` and `blocking` modes
+    let inner_fn_def = match args.mode {
+        RunMode::Domain | RunMode::Tokio => quote! {
+            async fn #inner_fn_name(#fn_args) #fn_output #where_clause {
+                #(#fn_body_stmts)*
+            }
+        },
+        RunMode::Blocking => quote! {
+            fn #inner_fn_name(#fn_args) #fn_output #where_clause {
+                #(#fn_body_stmts)*
+            }
+        },
+    };
+    let run = match args.mode {
+        RunMode::Domain => quote! {
+            #inner_fn_name(#(#call_args),*).await
+        },
+        RunMode::Tokio => quote! {
+            match ::ocaml_lwt_interop::domain_executor::tokio_rt()
+                .spawn(#inner_fn_name(#(#call_args),*))
+                .await
+            {
+                Ok(res) => res,
+                Err(err) => ::std::panic::resume_unwind(err.into_panic()),
+            }
+        },
+        RunMode::Blocking => quote! {
+            match ::ocaml_lwt_interop::domain_executor::tokio_rt()
+                .spawn_blocking(move || #inner_fn_name(#(#call_args),*))
+                .await
+            {
+                Ok(res) => res,
+                Err(err) => ::std::panic::resume_unwind(err.into_panic()),
+            }
+        },
+    };
+
+    // Each stub gets its own static limit, the permit is held by the body
+    let limit_ident = syn::Ident::new("__OLWTI_LIMIT", proc_macro2::Span::call_site());
+    let (limit_def, acquire_permit) = match (&args.max_concurrent, &args.overload) {
+        (None, _) => (quote! {}, quote! {}),
+        (Some(max_concurrent), overload) => (
+            quote! {
+                static #limit_ident: ::ocaml_lwt_interop::concurrency::ConcurrencyLimit =
+                    ::ocaml_lwt_interop::concurrency::ConcurrencyLimit::new(#max_concurrent);
+            },
+            match overload {
+                OverloadMode::Wait => {
+                    quote! { let _permit = #limit_ident.acquire().await; }
+                }
+                OverloadMode::Reject => quote! { let _permit = permit; },
+            },
+        ),
+    };
+
+    // The body is polled once right away, if it completes without suspending,
+    // already resolved promise is returned, and no task is spawned. Otherwise
+    // the task is named after the stub in the executor task list
+    let task_name = fn_name.to_string();
+    let spawn = quote! {
+        let mut body = ::std::boxed::Box::pin(async move { #acquire_permit #run });
+        match ::ocaml_lwt_interop::domain_executor::poll_once_with_runtime(gc, body.as_mut()) {
+            ::std::task::Poll::Ready(res) => #settled,
+            ::std::task::Poll::Pending => {
+                let (fut, resolver) = ::ocaml_lwt_interop::promise::Promise::new(gc);
+                let task = ::ocaml_lwt_interop::domain_executor::spawn_named_with_runtime(gc, #task_name, async move {
+                    let res = body.await;
+                    let gc = &::ocaml_lwt_interop::domain_executor::ocaml_runtime();
+                    #settle
+                });
+                task.detach();
+                fut
+            }
+        }
+    };
+    let spawn = if args.max_concurrent.is_some() && args.overload == OverloadMode::Reject
+    {
+        quote! {
+            match #limit_ident.try_acquire() {
+                ::std::option::Option::Some(permit) => { #spawn }
+                ::std::option::Option::None => ::ocaml_lwt_interop::concurrency::overloaded(gc),
+            }
+        }
+    } else {
+        spawn
+    };
+
+    Ok(quote! {
+        #(#other_attrs)*
+        #ocaml_func_attr
+        pub fn #fn_name(#(#outer_args),*) #fn_ret #where_clause {
+            #inner_fn_def
+            #arg_asserts
+            #limit_def
+            #spawn
+        }
+    })
+}
+
+fn blocking_impl(input: ItemFn) -> syn::Result<TokenStream2> {
+    if let Some(asyncness) = &input.sig.asyncness {
+        return Err(syn::Error::new_spanned(
+            asyncness,
+            "`blocking` can not be used on async functions, use `#[ocaml_lwt_interop::func]` instead",
+        ));
+    }
+    if let Some(param) = input.sig.generics.params.first() {
+        return Err(syn::Error::new_spanned(
+            param,
+            "blocking stubs can not have generic or lifetime parameters",
+        ));
+    }
+    let fn_name = &input.sig.ident;
+    let fn_body_stmts = &input.block.stmts;
+    let fn_args = &input.sig.inputs;
+    let fn_output = &input.sig.output;
+    let where_clause = &input.sig.generics.where_clause;
+    let (ocaml_func_attr, other_attrs) = split_ocaml_func_attr(input.attrs);
+    let args_info = stub_args(fn_args, "`blocking` can not be used on methods")?;
+    let outer_args: Vec<_> = args_info
+        .iter()
+        .map(|(ident, ty)| quote! { #ident: #ty })
+        .collect();
+    let call_args: Vec<_> = args_info.iter().map(|(ident, _)| ident).collect();
+    let inner_fn_name = syn::Ident::new("__inner", proc_macro2::Span::call_site());
+
+    // Arguments are converted by `#[ocaml::func]` before the lock is released,
+    // the result is converted after the lock is re-acquired
+    Ok(quote! {
         #(#other_attrs)*
         #ocaml_func_attr
-        pub fn #fn_name(#fn_args) #fn_ret {
-            async fn #inner_fn_name #fn_generics(#fn_args) #fn_output {
+        pub fn #fn_name(#(#outer_args),*) #fn_output #where_clause {
+            fn #inner_fn_name(#fn_args) #fn_output #where_clause {
                 #(#fn_body_stmts)*
             }
-            let (fut, resolver) = ::ocaml_lwt_interop::promise::Promise::new(gc);
-            let task = ::ocaml_lwt_interop::domain_executor::spawn_with_runtime(gc, async move {
-                let res = #inner_fn_name(#(#call_args),*).await;
-                let gc = &::ocaml_lwt_interop::domain_executor::ocaml_runtime();
-                resolver.resolve(gc, &res);
-            });
-            task.detach();
-            fut
+            ::ocaml_lwt_interop::blocking::Blocking::run(gc, move || {
+                #inner_fn_name(#(#call_args),*)
+            })
+        }
+    })
+}
+
+/// Arguments of `#[ocaml_lwt_interop::methods(...)]` attribute
+#[derive(Debug, Default)]
+struct MethodsArgs {
+    /// Name of generated OCaml module, defaults to the name of the type
+    module: Option<String>,
+    /// Prefix of generated stub names, defaults to snake case name of the type
+    prefix: Option<String>,
+}
+
+impl MethodsArgs {
+    fn parse(args: AttributeArgs) -> syn::Result<Self> {
+        let mut res = MethodsArgs::default();
+        for arg in args {
+            match arg {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                    path,
+                    lit: syn::Lit::Str(s),
+                    ..
+                })) if path.is_ident("module") || path.is_ident("prefix") => {
+                    if path.is_ident("module") {
+                        res.module = Some(s.value());
+                    } else {
+                        res.prefix = Some(s.value());
+                    }
+                }
+                arg => {
+                    return Err(syn::Error::new_spanned(
+                        arg,
+                        "unsupported argument, expected `module = \"...\"` or `prefix = \"...\"`",
+                    ))
+                }
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// Converts `CamelCase` identifier into `snake_case`
+fn to_snake_case(name: &str) -> String {
+    let mut res = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                res.push('_');
+            }
+            res.extend(c.to_lowercase());
+        } else {
+            res.push(c);
+        }
+    }
+    res
+}
+
+/// Replaces `Self` in `tokens` with `self_ty`, as generated stubs are free
+/// functions
+fn replace_self(tokens: TokenStream2, self_ty: &syn::Type) -> TokenStream2 {
+    tokens
+        .into_iter()
+        .map(|tt| match tt {
+            proc_macro2::TokenTree::Ident(ident) if ident == "Self" => {
+                quote! { #self_ty }
+            }
+            proc_macro2::TokenTree::Group(group) => {
+                let mut new_group = proc_macro2::Group::new(
+                    group.delimiter(),
+                    replace_self(group.stream(), self_ty),
+                );
+                new_group.set_span(group.span());
+                quote! { #new_group }
+            }
+            tt => quote! { #tt },
+        })
+        .collect()
+}
+
+fn methods_impl(args: MethodsArgs, input: ItemImpl) -> syn::Result<TokenStream2> {
+    if let Some((_, path, _)) = &input.trait_ {
+        return Err(syn::Error::new_spanned(
+            path,
+            "`methods` can not be used on trait implementations",
+        ));
+    }
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "`methods` can not be used on generic implementations",
+        ));
+    }
+    let self_ty = input.self_ty.as_ref();
+    let type_ident = match self_ty {
+        syn::Type::Path(type_path) => match type_path.path.segments.last() {
+            Some(segment) => segment.ident.clone(),
+            None => return Err(syn::Error::new_spanned(self_ty, "expected a type name")),
+        },
+        _ => return Err(syn::Error::new_spanned(self_ty, "expected a type name")),
+    };
+    let module_name = args.module.unwrap_or_else(|| type_ident.to_string());
+    let prefix = args
+        .prefix
+        .unwrap_or_else(|| to_snake_case(&type_ident.to_string()));
+    let mod_ident = syn::Ident::new(
+        &format!("__olwti_methods_{}", to_snake_case(&type_ident.to_string())),
+        proc_macro2::Span::call_site(),
+    );
+
+    let mut stubs = Vec::new();
+    let mut decls = Vec::new();
+    for item in &input.items {
+        let syn::ImplItem::Method(method) = item else {
+            continue;
+        };
+        if method.sig.asyncness.is_none() {
+            continue;
+        }
+        match method.sig.receiver() {
+            Some(syn::FnArg::Receiver(receiver))
+                if receiver.reference.is_some() && receiver.mutability.is_none() => {}
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &method.sig,
+                    "async methods exposed to OCaml must take `&self`",
+                ))
+            }
+        }
+        if !method.sig.generics.params.is_empty() {
+            return Err(syn::Error::new_spanned(
+                &method.sig.generics,
+                "async methods exposed to OCaml can not be generic",
+            ));
+        }
+
+        let method_name = &method.sig.ident;
+        let stub_name =
+            syn::Ident::new(&format!("{}_{}", prefix, method_name), method_name.span());
+        let args: Vec<_> = method
+            .sig
+            .inputs
+            .iter()
+            .filter_map(|arg| match arg {
+                syn::FnArg::Typed(pat_type) => Some(pat_type),
+                syn::FnArg::Receiver(_) => None,
+            })
+            .collect();
+        let arg_names = args
+            .iter()
+            .map(|pat_type| match pat_type.pat.as_ref() {
+                syn::Pat::Ident(pat_ident) => Ok(pat_ident.ident.clone()),
+                pat => Err(syn::Error::new_spanned(
+                    pat,
+                    "async methods exposed to OCaml must use plain identifiers as argument patterns",
+                )),
+            })
+            .collect::<syn::Result<Vec<_>>>()?;
+        let arg_types: Vec<_> = args
+            .iter()
+            .map(|pat_type| replace_self(pat_type.ty.to_token_stream(), self_ty))
+            .collect();
+        let output = replace_self(method.sig.output.to_token_stream(), self_ty);
+
+        // The DynBox is moved into the future, which keeps `self` alive until
+        // the method completes
+        let stub: ItemFn = syn::parse2(quote! {
+            #[ocaml_gen::func]
+            pub fn #stub_name(
+                __olwti_self: ::ocaml_rs_smartptr::ptr::DynBox<#self_ty>,
+                #(#arg_names: #arg_types),*
+            ) #output {
+                __olwti_self.coerce().#method_name(#(#arg_names),*).await
+            }
+        })?;
+        stubs.push(func_impl(FuncArgs::default(), stub)?);
+
+        let ocaml_name = method_name.to_string();
+        decls.push(quote! { decl_func!(#stub_name => #ocaml_name); });
+    }
+
+    Ok(quote! {
+        #input
+
+        #[doc(hidden)]
+        mod #mod_ident {
+            use super::*;
+
+            pub type T = ::ocaml_rs_smartptr::ptr::DynBox<#self_ty>;
+
+            #(#stubs)*
+
+            ::ocaml_rs_smartptr::ocaml_gen_bindings! {
+                decl_module!(#module_name, {
+                    decl_type!(T => "t");
+                    #(#decls)*
+                });
+            }
+        }
+    })
+}
+
+/// Arguments of `#[ocaml_lwt_interop::ocaml_trait(...)]` attribute
+#[derive(Debug, Default)]
+struct OCamlTraitArgs {
+    /// Name of generated struct, defaults to `OCaml<Trait>`
+    name: Option<String>,
+    /// OCaml record type, which generated struct is converted from
+    ocaml_type: Option<String>,
+}
+
+impl OCamlTraitArgs {
+    fn parse(args: AttributeArgs) -> syn::Result<Self> {
+        let mut res = OCamlTraitArgs::default();
+        for arg in args {
+            match arg {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                    path,
+                    lit: syn::Lit::Str(s),
+                    ..
+                })) if path.is_ident("name") || path.is_ident("ocaml_type") => {
+                    if path.is_ident("name") {
+                        res.name = Some(s.value());
+                    } else {
+                        res.ocaml_type = Some(s.value());
+                    }
+                }
+                arg => {
+                    return Err(syn::Error::new_spanned(
+                        arg,
+                        "unsupported argument, expected `name = \"...\"` or `ocaml_type = \"...\"`",
+                    ))
+                }
+            }
         }
+        Ok(res)
     }
 }
 
+fn ocaml_trait_impl(args: OCamlTraitArgs, input: ItemTrait) -> syn::Result<TokenStream2> {
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "`ocaml_trait` can not be used on generic traits",
+        ));
+    }
+    let trait_name = &input.ident;
+    let vis = &input.vis;
+    let struct_name = syn::Ident::new(
+        &args.name.unwrap_or_else(|| format!("OCaml{}", trait_name)),
+        trait_name.span(),
+    );
+
+    let mut fields = Vec::new();
+    let mut field_inits = Vec::new();
+    let mut methods = Vec::new();
+    let mut idx: usize = 0;
+    for item in &input.items {
+        let method = match item {
+            syn::TraitItem::Method(method) => method,
+            syn::TraitItem::Macro(_) | syn::TraitItem::Verbatim(_) => continue,
+            item => {
+                return Err(syn::Error::new_spanned(
+                    item,
+                    "`ocaml_trait` only supports traits consisting of methods",
+                ))
+            }
+        };
+        if method.sig.asyncness.is_none() {
+            if method.default.is_some() {
+                continue;
+            }
+            return Err(syn::Error::new_spanned(
+                &method.sig,
+                "methods without default implementation must be `async fn`",
+            ));
+        }
+        match method.sig.receiver() {
+            Some(syn::FnArg::Receiver(receiver))
+                if receiver.reference.is_some() && receiver.mutability.is_none() => {}
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &method.sig,
+                    "async methods implemented in OCaml must take `&self`",
+                ))
+            }
+        }
+        if !method.sig.generics.params.is_empty() {
+            return Err(syn::Error::new_spanned(
+                &method.sig.generics,
+                "async methods implemented in OCaml can not be generic",
+            ));
+        }
+
+        let method_name = &method.sig.ident;
+        let args = method
+            .sig
+            .inputs
+            .iter()
+            .filter_map(|arg| match arg {
+                syn::FnArg::Typed(pat_type) => Some(pat_type),
+                syn::FnArg::Receiver(_) => None,
+            })
+            .enumerate()
+            .map(|(i, pat_type)| {
+                let arg_name = syn::Ident::new(
+                    &format!("__olwti_arg{}", i),
+                    proc_macro2::Span::call_site(),
+                );
+                (arg_name, pat_type.ty.as_ref())
+            })
+            .collect::<Vec<_>>();
+        let arg_names: Vec<_> = args.iter().map(|(name, _)| name).collect();
+        let arg_types: Vec<_> = args.iter().map(|(_, typ)| typ).collect();
+        // Methods are expected to return `Lwt_result.t`, rejections are
+        // converted into `Err`, as there is no other way to report them
+        let ret = match &method.sig.output {
+            syn::ReturnType::Type(_, typ) if result_ok_type(typ).is_some() => {
+                typ.as_ref().clone()
+            }
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &method.sig,
+                    "async methods implemented in OCaml must return `Result<_, E>`, where `E: From<ocaml_lwt_interop::error::Error>`",
+                ))
+            }
+        };
+
+        fields.push(quote! {
+            #method_name: ::ocaml_lwt_interop::async_func::OCamlAsyncFunc<(#(#arg_types,)*), #ret>
+        });
+        field_inits.push(quote! {
+            #method_name: ::ocaml_lwt_interop::async_func::OCamlAsyncFunc::new(gc, v.field(#idx))
+        });
+        idx += 1;
+
+        methods.push(quote! {
+            async fn #method_name(&self #(, #arg_names: #arg_types)*) -> #ret {
+                let f = self.#method_name.clone();
+                self.__olwti_handle
+                    .spawn(async move { f.call_result((#(#arg_names,)*)).await })
+                    .await
+            }
+        });
+    }
+
+    let ocaml_desc = args.ocaml_type.map(|ocaml_type| {
+        quote! {
+            impl ::ocaml_gen::OCamlDesc for #struct_name {
+                fn ocaml_desc(_env: &::ocaml_gen::Env, _generics: &[&str]) -> String {
+                    #ocaml_type.to_string()
+                }
+
+                fn unique_id() -> u128 {
+                    ::ocaml_lwt_interop::async_func::named_type_unique_id(#ocaml_type)
+                }
+            }
+        }
+    });
+    let doc = format!(
+        "Implementation of [`{}`] backed by OCaml record of functions returning `Lwt.t`",
+        trait_name
+    );
+
+    Ok(quote! {
+        #input
+
+        #[doc = #doc]
+        #[derive(Clone)]
+        #vis struct #struct_name {
+            #(#fields,)*
+            __olwti_handle: ::ocaml_lwt_interop::domain_executor::Handle,
+        }
+
+        unsafe impl ::ocaml::FromValue for #struct_name {
+            fn from_value(v: ::ocaml::Value) -> Self {
+                let gc = unsafe { ::ocaml::Runtime::recover_handle() };
+                unsafe {
+                    Self {
+                        #(#field_inits,)*
+                        __olwti_handle: ::ocaml_lwt_interop::domain_executor::handle_from_runtime(gc),
+                    }
+                }
+            }
+        }
+
+        #ocaml_desc
+
+        impl #trait_name for #struct_name {
+            #(#methods)*
+        }
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -144,19 +1014,25 @@ mod tests {
                     resolver.resolve(&ocaml_runtime(), &());
                     ()
                 }
-                let (fut, resolver) = ::ocaml_lwt_interop::promise::Promise::new(gc);
-                let task = ::ocaml_lwt_interop::domain_executor::spawn_with_runtime(gc, async move {
-                    let res = __inner().await;
-                    let gc = &::ocaml_lwt_interop::domain_executor::ocaml_runtime();
-                    resolver.resolve(gc, &res);
-                });
-                task.detach();
-                fut
+                let mut body = ::std::boxed::Box::pin(async move { __inner().await });
+                match ::ocaml_lwt_interop::domain_executor::poll_once_with_runtime(gc, body.as_mut()) {
+                    ::std::task::Poll::Ready(res) => ::ocaml_lwt_interop::promise::Promise::resolved(gc, &res),
+                    ::std::task::Poll::Pending => {
+                        let (fut, resolver) = ::ocaml_lwt_interop::promise::Promise::new(gc);
+                        let task = ::ocaml_lwt_interop::domain_executor::spawn_named_with_runtime(gc, "lwti_tests_bench", async move {
+                            let res = body.await;
+                            let gc = &::ocaml_lwt_interop::domain_executor::ocaml_runtime();
+                            resolver.resolve(gc, &res);
+                        });
+                        task.detach();
+                        fut
+                    }
+                }
             }
         };
 
         let input_fn = syn::parse2::<ItemFn>(input).unwrap();
-        let actual = func_impl(input_fn);
+        let actual = func_impl(FuncArgs::default(), input_fn).unwrap();
         assert_tokens_eq(actual, expected);
     }
 
@@ -172,19 +1048,25 @@ mod tests {
             pub fn lwti_tests_bench() -> ::ocaml_lwt_interop::promise::Promise<()> {
                 async fn __inner() -> () {
                 }
-                let (fut, resolver) = ::ocaml_lwt_interop::promise::Promise::new(gc);
-                let task = ::ocaml_lwt_interop::domain_executor::spawn_with_runtime(gc, async move {
-                    let res = __inner().await;
-                    let gc = &::ocaml_lwt_interop::domain_executor::ocaml_runtime();
-                    resolver.resolve(gc, &res);
-                });
-                task.detach();
-                fut
+                let mut body = ::std::boxed::Box::pin(async move { __inner().await });
+                match ::ocaml_lwt_interop::domain_executor::poll_once_with_runtime(gc, body.as_mut()) {
+                    ::std::task::Poll::Ready(res) => ::ocaml_lwt_interop::promise::Promise::resolved(gc, &res),
+                    ::std::task::Poll::Pending => {
+                        let (fut, resolver) = ::ocaml_lwt_interop::promise::Promise::new(gc);
+                        let task = ::ocaml_lwt_interop::domain_executor::spawn_named_with_runtime(gc, "lwti_tests_bench", async move {
+                            let res = body.await;
+                            let gc = &::ocaml_lwt_interop::domain_executor::ocaml_runtime();
+                            resolver.resolve(gc, &res);
+                        });
+                        task.detach();
+                        fut
+                    }
+                }
             }
         };
 
         let input_fn = syn::parse2::<ItemFn>(input).unwrap();
-        let actual = func_impl(input_fn);
+        let actual = func_impl(FuncArgs::default(), input_fn).unwrap();
         assert_tokens_eq(actual, expected);
     }
 
@@ -199,19 +1081,755 @@ mod tests {
             pub fn lwti_tests_bench(arg1: String, args2: u32) -> ::ocaml_lwt_interop::promise::Promise<u64> {
                 async fn __inner(arg1: String, args2: u32) -> u64 {
                 }
-                let (fut, resolver) = ::ocaml_lwt_interop::promise::Promise::new(gc);
-                let task = ::ocaml_lwt_interop::domain_executor::spawn_with_runtime(gc, async move {
-                    let res = __inner(arg1, args2).await;
-                    let gc = &::ocaml_lwt_interop::domain_executor::ocaml_runtime();
-                    resolver.resolve(gc, &res);
+                fn __olwti_assert_send_static<T: Send + 'static>() {}
+                __olwti_assert_send_static::<String>();
+                __olwti_assert_send_static::<u32>();
+                let mut body = ::std::boxed::Box::pin(async move { __inner(arg1, args2).await });
+                match ::ocaml_lwt_interop::domain_executor::poll_once_with_runtime(gc, body.as_mut()) {
+                    ::std::task::Poll::Ready(res) => ::ocaml_lwt_interop::promise::Promise::resolved(gc, &res),
+                    ::std::task::Poll::Pending => {
+                        let (fut, resolver) = ::ocaml_lwt_interop::promise::Promise::new(gc);
+                        let task = ::ocaml_lwt_interop::domain_executor::spawn_named_with_runtime(gc, "lwti_tests_bench", async move {
+                            let res = body.await;
+                            let gc = &::ocaml_lwt_interop::domain_executor::ocaml_runtime();
+                            resolver.resolve(gc, &res);
+                        });
+                        task.detach();
+                        fut
+                    }
+                }
+            }
+        };
+
+        let input_fn = syn::parse2::<ItemFn>(input).unwrap();
+        let actual = func_impl(FuncArgs::default(), input_fn).unwrap();
+        assert_tokens_eq(actual, expected);
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_func_err_raise() {
+        let args: AttributeArgs = vec![syn::parse_quote!(err = "raise")];
+        let input: TokenStream2 = quote! {
+            pub fn lwti_tests_bench(arg1: u32) -> Result<u64, String> {}
+        };
+
+        let expected: TokenStream2 = quote! {
+            #[ocaml::func]
+            pub fn lwti_tests_bench(arg1: u32) -> ::ocaml_lwt_interop::promise::Promise<u64> {
+                async fn __inner(arg1: u32) -> Result<u64, String> {
+                }
+                fn __olwti_assert_send_static<T: Send + 'static>() {}
+                __olwti_assert_send_static::<u32>();
+                let mut body = ::std::boxed::Box::pin(async move { __inner(arg1).await });
+                match ::ocaml_lwt_interop::domain_executor::poll_once_with_runtime(gc, body.as_mut()) {
+                    ::std::task::Poll::Ready(res) => ::ocaml_lwt_interop::promise::Promise::settled(gc, &res),
+                    ::std::task::Poll::Pending => {
+                        let (fut, resolver) = ::ocaml_lwt_interop::promise::Promise::new(gc);
+                        let task = ::ocaml_lwt_interop::domain_executor::spawn_named_with_runtime(gc, "lwti_tests_bench", async move {
+                            let res = body.await;
+                            let gc = &::ocaml_lwt_interop::domain_executor::ocaml_runtime();
+                            resolver.settle(gc, &res);
+                        });
+                        task.detach();
+                        fut
+                    }
+                }
+            }
+        };
+
+        let input_fn = syn::parse2::<ItemFn>(input).unwrap();
+        let actual = func_impl(FuncArgs::parse(args).unwrap(), input_fn).unwrap();
+        assert_tokens_eq(actual, expected);
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_func_err_exn() {
+        let args: AttributeArgs = vec![syn::parse_quote!(err = "exn")];
+        let input: TokenStream2 = quote! {
+            pub fn lwti_tests_bench() -> std::result::Result<(), MyError> {}
+        };
+
+        let expected: TokenStream2 = quote! {
+            #[ocaml::func]
+            pub fn lwti_tests_bench() -> ::ocaml_lwt_interop::promise::Promise<()> {
+                async fn __inner() -> std::result::Result<(), MyError> {
+                }
+                let mut body = ::std::boxed::Box::pin(async move { __inner().await });
+                match ::ocaml_lwt_interop::domain_executor::poll_once_with_runtime(gc, body.as_mut()) {
+                    ::std::task::Poll::Ready(res) => ::ocaml_lwt_interop::promise::Promise::settled_exn(gc, &res),
+                    ::std::task::Poll::Pending => {
+                        let (fut, resolver) = ::ocaml_lwt_interop::promise::Promise::new(gc);
+                        let task = ::ocaml_lwt_interop::domain_executor::spawn_named_with_runtime(gc, "lwti_tests_bench", async move {
+                            let res = body.await;
+                            let gc = &::ocaml_lwt_interop::domain_executor::ocaml_runtime();
+                            resolver.settle_exn(gc, &res);
+                        });
+                        task.detach();
+                        fut
+                    }
+                }
+            }
+        };
+
+        let input_fn = syn::parse2::<ItemFn>(input).unwrap();
+        let actual = func_impl(FuncArgs::parse(args).unwrap(), input_fn).unwrap();
+        assert_tokens_eq(actual, expected);
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_func_err_requires_result() {
+        let args: AttributeArgs = vec![syn::parse_quote!(err = "raise")];
+        let input: TokenStream2 = quote! {
+            pub fn lwti_tests_bench() -> u64 {}
+        };
+
+        let input_fn = syn::parse2::<ItemFn>(input).unwrap();
+        let err = func_impl(FuncArgs::parse(args).unwrap(), input_fn).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`err` argument requires function to return `Result<T, E>`"
+        );
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_func_unknown_err_mode() {
+        let args: AttributeArgs = vec![syn::parse_quote!(err = "panic")];
+        assert!(FuncArgs::parse(args).is_err());
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_methods() {
+        let input: TokenStream2 = quote! {
+            impl HttpClient {
+                pub fn new() -> Self {
+                    HttpClient {}
+                }
+
+                pub async fn get(&self, key: String) -> Option<String> {
+                    None
+                }
+            }
+        };
+
+        let expected: TokenStream2 = quote! {
+            impl HttpClient {
+                pub fn new() -> Self {
+                    HttpClient {}
+                }
+
+                pub async fn get(&self, key: String) -> Option<String> {
+                    None
+                }
+            }
+
+            #[doc(hidden)]
+            mod __olwti_methods_http_client {
+                use super::*;
+
+                pub type T = ::ocaml_rs_smartptr::ptr::DynBox<HttpClient>;
+
+                #[ocaml_gen::func]
+                #[ocaml::func]
+                pub fn http_client_get(
+                    __olwti_self: ::ocaml_rs_smartptr::ptr::DynBox<HttpClient>,
+                    key: String
+                ) -> ::ocaml_lwt_interop::promise::Promise<Option<String> > {
+                    async fn __inner(
+                        __olwti_self: ::ocaml_rs_smartptr::ptr::DynBox<HttpClient>,
+                        key: String
+                    ) -> Option<String> {
+                        __olwti_self.coerce().get(key).await
+                    }
+                    fn __olwti_assert_send_static<T: Send + 'static>() {}
+                    __olwti_assert_send_static::<::ocaml_rs_smartptr::ptr::DynBox<HttpClient> >();
+                    __olwti_assert_send_static::<String>();
+                    let mut body = ::std::boxed::Box::pin(async move { __inner(__olwti_self, key).await });
+                    match ::ocaml_lwt_interop::domain_executor::poll_once_with_runtime(gc, body.as_mut()) {
+                        ::std::task::Poll::Ready(res) => ::ocaml_lwt_interop::promise::Promise::resolved(gc, &res),
+                        ::std::task::Poll::Pending => {
+                            let (fut, resolver) = ::ocaml_lwt_interop::promise::Promise::new(gc);
+                            let task = ::ocaml_lwt_interop::domain_executor::spawn_named_with_runtime(gc, "http_client_get", async move {
+                                let res = body.await;
+                                let gc = &::ocaml_lwt_interop::domain_executor::ocaml_runtime();
+                                resolver.resolve(gc, &res);
+                            });
+                            task.detach();
+                            fut
+                        }
+                    }
+                }
+
+                ::ocaml_rs_smartptr::ocaml_gen_bindings! {
+                    decl_module!("HttpClient", {
+                        decl_type!(T => "t");
+                        decl_func!(http_client_get => "get");
+                    });
+                }
+            }
+        };
+
+        let input_impl = syn::parse2::<ItemImpl>(input).unwrap();
+        let actual = methods_impl(MethodsArgs::default(), input_impl).unwrap();
+        assert_tokens_eq(actual, expected);
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_methods_replaces_self() {
+        let args: AttributeArgs = vec![
+            syn::parse_quote!(module = "Conn"),
+            syn::parse_quote!(prefix = "my_conn"),
+        ];
+        let input: TokenStream2 = quote! {
+            impl Client {
+                async fn fork(&self, other: DynBox<Self>) {}
+            }
+        };
+
+        let input_impl = syn::parse2::<ItemImpl>(input).unwrap();
+        let actual = methods_impl(MethodsArgs::parse(args).unwrap(), input_impl)
+            .unwrap()
+            .to_string();
+        assert!(actual.contains(&quote! { pub fn my_conn_fork }.to_string()));
+        assert!(actual.contains(&quote! { other: DynBox<Client> }.to_string()));
+        assert!(actual.contains("decl_module ! (\"Conn\""));
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_methods_requires_shared_self() {
+        let input: TokenStream2 = quote! {
+            impl Client {
+                async fn reset(&mut self) {}
+            }
+        };
+
+        let input_impl = syn::parse2::<ItemImpl>(input).unwrap();
+        let err = methods_impl(MethodsArgs::default(), input_impl).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "async methods exposed to OCaml must take `&self`"
+        );
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_ocaml_trait() {
+        let input: TokenStream2 = quote! {
+            pub trait Backend {
+                async fn fetch(&self, key: String) -> Result<String, String>;
+                async fn ping(&self) -> Result<(), String>;
+            }
+        };
+
+        let expected: TokenStream2 = quote! {
+            pub trait Backend {
+                async fn fetch(&self, key: String) -> Result<String, String>;
+                async fn ping(&self) -> Result<(), String>;
+            }
+
+            #[doc = "Implementation of [`Backend`] backed by OCaml record of functions returning `Lwt.t`"]
+            #[derive(Clone)]
+            pub struct OCamlBackend {
+                fetch: ::ocaml_lwt_interop::async_func::OCamlAsyncFunc<(String,), Result<String, String> >,
+                ping: ::ocaml_lwt_interop::async_func::OCamlAsyncFunc<(), Result<(), String> >,
+                __olwti_handle: ::ocaml_lwt_interop::domain_executor::Handle,
+            }
+
+            unsafe impl ::ocaml::FromValue for OCamlBackend {
+                fn from_value(v: ::ocaml::Value) -> Self {
+                    let gc = unsafe { ::ocaml::Runtime::recover_handle() };
+                    unsafe {
+                        Self {
+                            fetch: ::ocaml_lwt_interop::async_func::OCamlAsyncFunc::new(gc, v.field(0usize)),
+                            ping: ::ocaml_lwt_interop::async_func::OCamlAsyncFunc::new(gc, v.field(1usize)),
+                            __olwti_handle: ::ocaml_lwt_interop::domain_executor::handle_from_runtime(gc),
+                        }
+                    }
+                }
+            }
+
+            impl Backend for OCamlBackend {
+                async fn fetch(&self, __olwti_arg0: String) -> Result<String, String> {
+                    let f = self.fetch.clone();
+                    self.__olwti_handle
+                        .spawn(async move { f.call_result((__olwti_arg0,)).await })
+                        .await
+                }
+                async fn ping(&self) -> Result<(), String> {
+                    let f = self.ping.clone();
+                    self.__olwti_handle
+                        .spawn(async move { f.call_result(()).await })
+                        .await
+                }
+            }
+        };
+
+        let input_trait = syn::parse2::<ItemTrait>(input).unwrap();
+        let actual = ocaml_trait_impl(OCamlTraitArgs::default(), input_trait).unwrap();
+        assert_tokens_eq(actual, expected);
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_ocaml_trait_requires_result() {
+        let input: TokenStream2 = quote! {
+            trait Backend {
+                async fn ping(&self);
+            }
+        };
+
+        let input_trait = syn::parse2::<ItemTrait>(input).unwrap();
+        let err = ocaml_trait_impl(OCamlTraitArgs::default(), input_trait).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "async methods implemented in OCaml must return `Result<_, E>`, where `E: From<ocaml_lwt_interop::error::Error>`"
+        );
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_ocaml_trait_requires_async() {
+        let input: TokenStream2 = quote! {
+            trait Backend {
+                fn fetch(&self, key: String) -> String;
+            }
+        };
+
+        let input_trait = syn::parse2::<ItemTrait>(input).unwrap();
+        let err = ocaml_trait_impl(OCamlTraitArgs::default(), input_trait).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "methods without default implementation must be `async fn`"
+        );
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_func_with_patterns() {
+        let input: TokenStream2 = quote! {
+            pub fn lwti_tests_bench((a, b): (u32, u32), mut c: u32) -> u32
+            where
+                u32: Send,
+            {
+                c += a + b;
+                c
+            }
+        };
+
+        let expected: TokenStream2 = quote! {
+            #[ocaml::func]
+            pub fn lwti_tests_bench(__olwti_arg0: (u32, u32), c: u32) -> ::ocaml_lwt_interop::promise::Promise<u32>
+            where
+                u32: Send,
+            {
+                async fn __inner((a, b): (u32, u32), mut c: u32) -> u32
+                where
+                    u32: Send,
+                {
+                    c += a + b;
+                    c
+                }
+                fn __olwti_assert_send_static<T: Send + 'static>() {}
+                __olwti_assert_send_static::<(u32, u32)>();
+                __olwti_assert_send_static::<u32>();
+                let mut body = ::std::boxed::Box::pin(async move { __inner(__olwti_arg0, c).await });
+                match ::ocaml_lwt_interop::domain_executor::poll_once_with_runtime(gc, body.as_mut()) {
+                    ::std::task::Poll::Ready(res) => ::ocaml_lwt_interop::promise::Promise::resolved(gc, &res),
+                    ::std::task::Poll::Pending => {
+                        let (fut, resolver) = ::ocaml_lwt_interop::promise::Promise::new(gc);
+                        let task = ::ocaml_lwt_interop::domain_executor::spawn_named_with_runtime(gc, "lwti_tests_bench", async move {
+                            let res = body.await;
+                            let gc = &::ocaml_lwt_interop::domain_executor::ocaml_runtime();
+                            resolver.resolve(gc, &res);
+                        });
+                        task.detach();
+                        fut
+                    }
+                }
+            }
+        };
+
+        let input_fn = syn::parse2::<ItemFn>(input).unwrap();
+        let actual = func_impl(FuncArgs::default(), input_fn).unwrap();
+        assert_tokens_eq(actual, expected);
+    }
+
+    fn func_impl_err(input: TokenStream2) -> String {
+        let input_fn = syn::parse2::<ItemFn>(input).unwrap();
+        func_impl(FuncArgs::default(), input_fn)
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_func_rejects_references() {
+        let err = func_impl_err(quote! {
+            pub fn lwti_tests_bench(s: &str) {}
+        });
+        assert_eq!(
+            err,
+            "arguments of async stubs can not be references, as they must outlive the stub call, use owned types instead"
+        );
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_func_rejects_impl_trait() {
+        let err = func_impl_err(quote! {
+            pub fn lwti_tests_bench(s: impl Send) {}
+        });
+        assert_eq!(
+            err,
+            "arguments of async stubs can not be `impl Trait`, use concrete types instead"
+        );
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_func_rejects_self() {
+        let err = func_impl_err(quote! {
+            pub fn lwti_tests_bench(&self) {}
+        });
+        assert_eq!(
+            err,
+            "`func` can not be used on methods, use `#[ocaml_lwt_interop::methods]` on the impl block instead"
+        );
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_func_rejects_generics() {
+        let err = func_impl_err(quote! {
+            pub fn lwti_tests_bench<'a, T>(s: T) {}
+        });
+        assert_eq!(
+            err,
+            "async stubs can not have generic or lifetime parameters"
+        );
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_func_error_span() {
+        let input: TokenStream2 = quote! {
+            pub fn lwti_tests_bench(a: u32, b: &u32) {}
+        };
+        let input_fn = syn::parse2::<ItemFn>(input).unwrap();
+        let arg_span = match &input_fn.sig.inputs[1] {
+            syn::FnArg::Typed(pat_type) => pat_type.ty.span(),
+            _ => unreachable!(),
+        };
+        let err = func_impl(FuncArgs::default(), input_fn).unwrap_err();
+        assert_eq!(format!("{:?}", err.span()), format!("{:?}", arg_span));
+    }
+
+    #[test]
+    fn test_paths_equal_with_arguments() {
+        let path =
+            |tokens: TokenStream2| syn::parse2::<syn::TypePath>(tokens).unwrap().path;
+        assert!(paths_equal(
+            &path(quote! { ocaml::func }),
+            &path(quote! { ocaml::func })
+        ));
+        assert!(paths_equal(
+            &path(quote! { a::b<u32>::c }),
+            &path(quote! { a::b<u32>::c })
+        ));
+        assert!(!paths_equal(
+            &path(quote! { a::b<u32>::c }),
+            &path(quote! { a::b<u64>::c })
+        ));
+        assert!(paths_equal(
+            &path(quote! { Fn(u32) -> u32 }),
+            &path(quote! { Fn(u32) -> u32 })
+        ));
+        assert!(!paths_equal(
+            &path(quote! { Fn(u32) -> u32 }),
+            &path(quote! { Fn(u32) })
+        ));
+        assert!(!paths_equal(
+            &path(quote! { a::b<u32> }),
+            &path(quote! { a::b })
+        ));
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_func_tokio() {
+        let args: AttributeArgs = vec![syn::parse_quote!(tokio)];
+        let input: TokenStream2 = quote! {
+            pub fn lwti_tests_bench() -> u64 {
+                42
+            }
+        };
+
+        let expected: TokenStream2 = quote! {
+            #[ocaml::func]
+            pub fn lwti_tests_bench() -> ::ocaml_lwt_interop::promise::Promise<u64> {
+                async fn __inner() -> u64 {
+                    42
+                }
+                let mut body = ::std::boxed::Box::pin(async move {
+                    match ::ocaml_lwt_interop::domain_executor::tokio_rt()
+                        .spawn(__inner())
+                        .await
+                    {
+                        Ok(res) => res,
+                        Err(err) => ::std::panic::resume_unwind(err.into_panic()),
+                    }
                 });
-                task.detach();
-                fut
+                match ::ocaml_lwt_interop::domain_executor::poll_once_with_runtime(gc, body.as_mut()) {
+                    ::std::task::Poll::Ready(res) => ::ocaml_lwt_interop::promise::Promise::resolved(gc, &res),
+                    ::std::task::Poll::Pending => {
+                        let (fut, resolver) = ::ocaml_lwt_interop::promise::Promise::new(gc);
+                        let task = ::ocaml_lwt_interop::domain_executor::spawn_named_with_runtime(gc, "lwti_tests_bench", async move {
+                            let res = body.await;
+                            let gc = &::ocaml_lwt_interop::domain_executor::ocaml_runtime();
+                            resolver.resolve(gc, &res);
+                        });
+                        task.detach();
+                        fut
+                    }
+                }
+            }
+        };
+
+        let input_fn = syn::parse2::<ItemFn>(input).unwrap();
+        let actual = func_impl(FuncArgs::parse(args).unwrap(), input_fn).unwrap();
+        assert_tokens_eq(actual, expected);
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_func_blocking() {
+        let args: AttributeArgs = vec![
+            syn::parse_quote!(blocking),
+            syn::parse_quote!(err = "raise"),
+        ];
+        let input: TokenStream2 = quote! {
+            pub fn lwti_tests_bench(path: String) -> Result<u64, String> {
+                Ok(path.len() as u64)
+            }
+        };
+
+        let expected: TokenStream2 = quote! {
+            #[ocaml::func]
+            pub fn lwti_tests_bench(path: String) -> ::ocaml_lwt_interop::promise::Promise<u64> {
+                fn __inner(path: String) -> Result<u64, String> {
+                    Ok(path.len() as u64)
+                }
+                fn __olwti_assert_send_static<T: Send + 'static>() {}
+                __olwti_assert_send_static::<String>();
+                let mut body = ::std::boxed::Box::pin(async move {
+                    match ::ocaml_lwt_interop::domain_executor::tokio_rt()
+                        .spawn_blocking(move || __inner(path))
+                        .await
+                    {
+                        Ok(res) => res,
+                        Err(err) => ::std::panic::resume_unwind(err.into_panic()),
+                    }
+                });
+                match ::ocaml_lwt_interop::domain_executor::poll_once_with_runtime(gc, body.as_mut()) {
+                    ::std::task::Poll::Ready(res) => ::ocaml_lwt_interop::promise::Promise::settled(gc, &res),
+                    ::std::task::Poll::Pending => {
+                        let (fut, resolver) = ::ocaml_lwt_interop::promise::Promise::new(gc);
+                        let task = ::ocaml_lwt_interop::domain_executor::spawn_named_with_runtime(gc, "lwti_tests_bench", async move {
+                            let res = body.await;
+                            let gc = &::ocaml_lwt_interop::domain_executor::ocaml_runtime();
+                            resolver.settle(gc, &res);
+                        });
+                        task.detach();
+                        fut
+                    }
+                }
+            }
+        };
+
+        let input_fn = syn::parse2::<ItemFn>(input).unwrap();
+        let actual = func_impl(FuncArgs::parse(args).unwrap(), input_fn).unwrap();
+        assert_tokens_eq(actual, expected);
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_func_tokio_rejects_ocaml_runtime() {
+        let args: AttributeArgs = vec![syn::parse_quote!(tokio)];
+        let input: TokenStream2 = quote! {
+            pub fn lwti_tests_bench() {
+                let gc = ocaml_lwt_interop::domain_executor::ocaml_runtime();
+            }
+        };
+
+        let input_fn = syn::parse2::<ItemFn>(input).unwrap();
+        let err = func_impl(FuncArgs::parse(args).unwrap(), input_fn).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`ocaml_runtime` can not be used in `tokio` or `blocking` mode, as the body does not run on OCaml domain executor"
+        );
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_func_tokio_allows_ocaml_runtime_name() {
+        let args: AttributeArgs = vec![syn::parse_quote!(tokio)];
+        let input: TokenStream2 = quote! {
+            pub fn lwti_tests_bench(ocaml_runtime: u64) -> u64 {
+                ocaml_runtime + 1
+            }
+        };
+
+        let input_fn = syn::parse2::<ItemFn>(input).unwrap();
+        assert!(func_impl(FuncArgs::parse(args).unwrap(), input_fn).is_ok());
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_func_tokio_and_blocking() {
+        let args: AttributeArgs =
+            vec![syn::parse_quote!(tokio), syn::parse_quote!(blocking)];
+        assert!(FuncArgs::parse(args).is_err());
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_func_max_concurrent() {
+        let args: AttributeArgs = vec![syn::parse_quote!(max_concurrent = 64)];
+        let input: TokenStream2 = quote! {
+            pub fn lwti_tests_bench() -> u64 {
+                42
+            }
+        };
+
+        let expected: TokenStream2 = quote! {
+            #[ocaml::func]
+            pub fn lwti_tests_bench() -> ::ocaml_lwt_interop::promise::Promise<u64> {
+                async fn __inner() -> u64 {
+                    42
+                }
+                static __OLWTI_LIMIT: ::ocaml_lwt_interop::concurrency::ConcurrencyLimit =
+                    ::ocaml_lwt_interop::concurrency::ConcurrencyLimit::new(64usize);
+                let mut body = ::std::boxed::Box::pin(async move {
+                    let _permit = __OLWTI_LIMIT.acquire().await;
+                    __inner().await
+                });
+                match ::ocaml_lwt_interop::domain_executor::poll_once_with_runtime(gc, body.as_mut()) {
+                    ::std::task::Poll::Ready(res) => ::ocaml_lwt_interop::promise::Promise::resolved(gc, &res),
+                    ::std::task::Poll::Pending => {
+                        let (fut, resolver) = ::ocaml_lwt_interop::promise::Promise::new(gc);
+                        let task = ::ocaml_lwt_interop::domain_executor::spawn_named_with_runtime(gc, "lwti_tests_bench", async move {
+                            let res = body.await;
+                            let gc = &::ocaml_lwt_interop::domain_executor::ocaml_runtime();
+                            resolver.resolve(gc, &res);
+                        });
+                        task.detach();
+                        fut
+                    }
+                }
             }
         };
 
         let input_fn = syn::parse2::<ItemFn>(input).unwrap();
-        let actual = func_impl(input_fn);
+        let actual = func_impl(FuncArgs::parse(args).unwrap(), input_fn).unwrap();
         assert_tokens_eq(actual, expected);
     }
+
+    #[test]
+    fn test_ocaml_lwt_interop_func_max_concurrent_reject() {
+        let args: AttributeArgs = vec![
+            syn::parse_quote!(max_concurrent = 1),
+            syn::parse_quote!(overload = "reject"),
+        ];
+        let input: TokenStream2 = quote! {
+            pub fn lwti_tests_bench() -> u64 {
+                42
+            }
+        };
+
+        let expected: TokenStream2 = quote! {
+            #[ocaml::func]
+            pub fn lwti_tests_bench() -> ::ocaml_lwt_interop::promise::Promise<u64> {
+                async fn __inner() -> u64 {
+                    42
+                }
+                static __OLWTI_LIMIT: ::ocaml_lwt_interop::concurrency::ConcurrencyLimit =
+                    ::ocaml_lwt_interop::concurrency::ConcurrencyLimit::new(1usize);
+                match __OLWTI_LIMIT.try_acquire() {
+                    ::std::option::Option::Some(permit) => {
+                        let mut body = ::std::boxed::Box::pin(async move {
+                            let _permit = permit;
+                            __inner().await
+                        });
+                        match ::ocaml_lwt_interop::domain_executor::poll_once_with_runtime(gc, body.as_mut()) {
+                            ::std::task::Poll::Ready(res) => ::ocaml_lwt_interop::promise::Promise::resolved(gc, &res),
+                            ::std::task::Poll::Pending => {
+                                let (fut, resolver) = ::ocaml_lwt_interop::promise::Promise::new(gc);
+                                let task = ::ocaml_lwt_interop::domain_executor::spawn_named_with_runtime(gc, "lwti_tests_bench", async move {
+                                    let res = body.await;
+                                    let gc = &::ocaml_lwt_interop::domain_executor::ocaml_runtime();
+                                    resolver.resolve(gc, &res);
+                                });
+                                task.detach();
+                                fut
+                            }
+                        }
+                    }
+                    ::std::option::Option::None => ::ocaml_lwt_interop::concurrency::overloaded(gc),
+                }
+            }
+        };
+
+        let input_fn = syn::parse2::<ItemFn>(input).unwrap();
+        let actual = func_impl(FuncArgs::parse(args).unwrap(), input_fn).unwrap();
+        assert_tokens_eq(actual, expected);
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_func_max_concurrent_invalid() {
+        let args: AttributeArgs = vec![syn::parse_quote!(max_concurrent = 0)];
+        assert!(FuncArgs::parse(args).is_err());
+        let args: AttributeArgs = vec![syn::parse_quote!(max_concurrent = "64")];
+        assert!(FuncArgs::parse(args).is_err());
+        let args: AttributeArgs = vec![
+            syn::parse_quote!(max_concurrent = 64),
+            syn::parse_quote!(overload = "drop"),
+        ];
+        assert!(FuncArgs::parse(args).is_err());
+        let max = proc_macro2::Literal::usize_unsuffixed(MAX_PERMITS + 1);
+        let args: AttributeArgs = vec![syn::parse_quote!(max_concurrent = #max)];
+        let err = FuncArgs::parse(args).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("`max_concurrent` can not exceed {}", MAX_PERMITS)
+        );
+        let args: AttributeArgs = vec![syn::parse_quote!(overload = "reject")];
+        let err = FuncArgs::parse(args).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`overload` requires `max_concurrent` argument"
+        );
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_blocking() {
+        let input: TokenStream2 = quote! {
+            #[ocaml_gen::func]
+            pub fn lwti_tests_hash(data: String, (a, b): (u8, u8)) -> i64 {
+                data.len() as i64 + a as i64 + b as i64
+            }
+        };
+
+        let expected: TokenStream2 = quote! {
+            #[ocaml_gen::func]
+            #[ocaml::func]
+            pub fn lwti_tests_hash(data: String, __olwti_arg1: (u8, u8)) -> i64 {
+                fn __inner(data: String, (a, b): (u8, u8)) -> i64 {
+                    data.len() as i64 + a as i64 + b as i64
+                }
+                ::ocaml_lwt_interop::blocking::Blocking::run(gc, move || {
+                    __inner(data, __olwti_arg1)
+                })
+            }
+        };
+
+        let input_fn = syn::parse2::<ItemFn>(input).unwrap();
+        let actual = blocking_impl(input_fn).unwrap();
+        assert_tokens_eq(actual, expected);
+    }
+
+    #[test]
+    fn test_ocaml_lwt_interop_blocking_rejects_references() {
+        let input: TokenStream2 = quote! {
+            pub fn lwti_tests_hash(data: &str) -> i64 {
+                0
+            }
+        };
+
+        let input_fn = syn::parse2::<ItemFn>(input).unwrap();
+        assert!(blocking_impl(input_fn).is_err());
+    }
 }
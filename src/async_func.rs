@@ -1,13 +1,28 @@
-//! An extension on top of `OCamlFunc` for asynchronous functions
+//! An extension on top of `OCamlFunc` for asynchronous functions, and its
+//! inverse, i.e. Rust async closures, which are passed to OCaml as functions
+//! returning `'a Lwt.t`
 
-use std::future::IntoFuture;
+use std::future::{Future, IntoFuture};
+use std::hash::Hash;
 use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::sync::Arc;
 
-use crate::domain_executor::ocaml_runtime;
-use crate::promise::{Promise, PromiseFuture};
-use ocaml_gen::OCamlDesc;
+use crate::domain_executor::{ocaml_runtime, spawn_lwt};
+use crate::error::Error;
+use crate::promise::{LwtResultFuture, Promise, PromiseFuture};
+use highway::{HighwayHash, HighwayHasher};
+use ocaml::ToValue;
+use ocaml_gen::{const_random, OCamlDesc};
 use ocaml_rs_smartptr::callable::Callable;
 use ocaml_rs_smartptr::func::OCamlFunc;
+use ocaml_rs_smartptr::ptr::DynBox;
+
+// OCaml callbacks are registered in ../lib/Rust_async.ml
+ocaml::import! {
+    // `olwti_rust_closure` wraps `RustClosure` into curried OCaml function of
+    // given arity
+    fn olwti_rust_closure(closure: DynBox<RustClosure>, arity: isize) -> ocaml::Value;
+}
 
 /// An extension on top of `OCamlFunc` for asynchronous functions, i.e. if
 /// `OCamlFunc` wraps some OCaml function returning the value itself,
@@ -46,6 +61,22 @@ where
     }
 }
 
+impl<Args, T, E> OCamlAsyncFunc<Args, Result<T, E>>
+where
+    Args: Callable<Promise<Result<T, E>>>,
+    T: ocaml::FromValue + Send + 'static,
+    E: ocaml::FromValue + From<Error> + Send + 'static,
+    Promise<Result<T, E>>: ocaml::FromValue + OCamlDesc,
+{
+    /// Calls inner OCamlFunc, assuming it's return value is
+    /// `('a, 'e) Lwt_result.t`, converts result into `LwtResultFuture`, which
+    /// resolves to `Result<T, E>`. Rejection of the promise is converted into
+    /// `E`.
+    pub fn call_result(&self, args: Args) -> LwtResultFuture<T, E> {
+        self.call(args).into_result()
+    }
+}
+
 impl<Args, Ret> OCamlDesc for OCamlAsyncFunc<Args, Ret>
 where
     Args: Callable<Promise<Ret>> + Send,
@@ -60,3 +91,192 @@ where
         Args::unique_id()
     }
 }
+
+/// Arguments of [`RustAsyncFunc`], i.e. a tuple of values convertible from
+/// OCaml. Unit `()` corresponds to OCaml function taking `unit`.
+pub trait ClosureArgs: Sized + Send + 'static {
+    /// Number of arguments of OCaml function
+    const ARITY: usize;
+
+    /// Converts OCaml block of arguments, packed by OCaml function wrapping
+    /// [`RustClosure`], into the tuple.
+    ///
+    /// # Safety
+    ///
+    /// `args` must be a block of at least [`ClosureArgs::ARITY`] fields.
+    unsafe fn from_args(args: ocaml::Value) -> Self;
+}
+
+impl ClosureArgs for () {
+    const ARITY: usize = 0;
+
+    unsafe fn from_args(_args: ocaml::Value) -> Self {}
+}
+
+macro_rules! impl_closure_args {
+    ($arity:literal; $($idx:literal => $ty:ident),+) => {
+        impl<$($ty),+> ClosureArgs for ($($ty,)+)
+        where
+            $($ty: ocaml::FromValue + Send + 'static),+
+        {
+            const ARITY: usize = $arity;
+
+            unsafe fn from_args(args: ocaml::Value) -> Self {
+                ($(<$ty as ocaml::FromValue>::from_value(args.field($idx)),)+)
+            }
+        }
+    };
+}
+
+impl_closure_args!(1; 0 => A);
+impl_closure_args!(2; 0 => A, 1 => B);
+impl_closure_args!(3; 0 => A, 1 => B, 2 => C);
+impl_closure_args!(4; 0 => A, 1 => B, 2 => C, 3 => D);
+
+type BoxedAsyncFn<Args, Ret> =
+    dyn Fn(Args) -> futures_lite::future::Boxed<Ret> + Send + Sync + 'static;
+
+/// Rust async closure, which can be passed to OCaml as a first-class function
+/// returning `'a Lwt.t`, i.e. an inverse of [`OCamlAsyncFunc`].
+///
+/// Each call from OCaml spawns the future, returned by the closure, onto OCaml
+/// domain executor and returns a promise, which is resolved with its output.
+/// `Args` is a tuple of up to 4 arguments, see [`ClosureArgs`].
+///
+/// On OCaml side the closure is described as `'f Rust_async.Closure.fn`, an
+/// abbreviation for `'f`. Arity of an external is determined by the arrows
+/// in its declared type, and parentheses around returned function type do not
+/// count, so without the abbreviation the stub would be called with the
+/// arguments of the closure as well.
+///
+/// ```rust
+/// use ocaml_lwt_interop::async_func::RustAsyncFunc;
+///
+/// #[ocaml::func]
+/// pub fn make_adder(n: i64) -> RustAsyncFunc<(i64,), i64> {
+///     RustAsyncFunc::new(move |(x,)| async move { x + n })
+/// }
+/// ```
+///
+/// Can be declared from OCaml side as follows:
+///
+/// ```ocaml
+/// external make_adder : int64 -> (int64 -> int64 Lwt.t) Rust_async.Closure.fn
+///   = "make_adder"
+/// ```
+pub struct RustAsyncFunc<Args, Ret> {
+    inner: Arc<BoxedAsyncFn<Args, Ret>>,
+}
+
+assert_impl_all!(RustAsyncFunc<(ocaml::Value,), ocaml::Value>: Send, Sync);
+
+impl<Args, Ret> Clone for RustAsyncFunc<Args, Ret> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<Args, Ret> RustAsyncFunc<Args, Ret>
+where
+    Args: ClosureArgs,
+    Ret: ocaml::ToValue + Send + 'static,
+{
+    /// Creates a new `RustAsyncFunc` out of async closure `f`.
+    pub fn new<F, Fut>(f: F) -> Self
+    where
+        F: Fn(Args) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Ret> + Send + 'static,
+    {
+        Self {
+            inner: Arc::new(move |args| {
+                Box::pin(f(args)) as futures_lite::future::Boxed<Ret>
+            }),
+        }
+    }
+
+    /// Calls the closure from Rust.
+    pub async fn call(&self, args: Args) -> Ret {
+        (self.inner)(args).await
+    }
+}
+
+type ErasedAsyncFn = dyn Fn(&ocaml::Runtime, ocaml::Value) -> ocaml::Value + Send + Sync;
+
+/// Type-erased [`RustAsyncFunc`], which is exposed to OCaml as
+/// `Rust_async.Closure.t`. Takes OCaml block of arguments and returns
+/// `'a Lwt.t`.
+pub struct RustClosure {
+    call: Box<ErasedAsyncFn>,
+}
+
+impl RustClosure {
+    /// Calls the closure with OCaml block of arguments, returns `'a Lwt.t`.
+    ///
+    /// # Safety
+    ///
+    /// `args` must be a block of arguments matching closure arity.
+    pub(crate) unsafe fn call(
+        &self,
+        gc: &ocaml::Runtime,
+        args: ocaml::Value,
+    ) -> ocaml::Value {
+        (self.call)(gc, args)
+    }
+}
+
+unsafe impl<Args, Ret> ocaml::ToValue for RustAsyncFunc<Args, Ret>
+where
+    Args: ClosureArgs,
+    Ret: ocaml::ToValue + Send + 'static,
+{
+    fn to_value(&self, gc: &ocaml::Runtime) -> ocaml::Value {
+        let f = self.inner.clone();
+        let closure = RustClosure {
+            call: Box::new(move |gc: &ocaml::Runtime, args: ocaml::Value| {
+                let args = unsafe { Args::from_args(args) };
+                spawn_lwt(gc, f(args)).to_value(gc)
+            }),
+        };
+        unsafe {
+            olwti_rust_closure(gc, DynBox::new_shared(closure), Args::ARITY as isize)
+        }
+        .expect("olwti_rust_closure has thrown an exception")
+    }
+}
+
+impl<Args, Ret> OCamlDesc for RustAsyncFunc<Args, Ret>
+where
+    Args: Callable<Promise<Ret>> + Send,
+    Ret: Send,
+    Promise<Ret>: ocaml::FromValue + OCamlDesc + Send,
+{
+    fn ocaml_desc(env: &::ocaml_gen::Env, generics: &[&str]) -> String {
+        format!(
+            "({}) Rust_async.Closure.fn",
+            Args::ocaml_desc(env, generics)
+        )
+    }
+
+    fn unique_id() -> u128 {
+        named_type_unique_id("Rust_async.Closure.fn") ^ Args::unique_id()
+    }
+}
+
+/// Returns `OCamlDesc::unique_id` for a type, which is described by `name` on
+/// OCaml side. Used for types with fixed OCaml names across the crate, and by
+/// code generated by `#[ocaml_lwt_interop::ocaml_trait]`, hence `pub`.
+#[doc(hidden)]
+pub fn named_type_unique_id(name: &str) -> u128 {
+    let key = highway::Key([
+        const_random!(u64),
+        const_random!(u64),
+        const_random!(u64),
+        const_random!(u64),
+    ]);
+    let mut hasher = HighwayHasher::new(key);
+    name.hash(&mut hasher);
+    let result = hasher.finalize128();
+    (result[0] as u128) | ((result[1] as u128) << 64)
+}
@@ -0,0 +1,79 @@
+//! Running synchronous Rust code with OCaml domain lock released.
+//!
+//! # Overview
+//!
+//! Synchronous stubs, doing blocking or CPU-heavy work (compression, file
+//! hashing, etc.), hold OCaml domain lock for the whole duration of the call,
+//! so other OCaml threads, including `Lwt_preemptive` workers, can not run
+//! meanwhile. [`Blocking`] releases the lock for its lifetime and re-acquires
+//! it once dropped, even if the code panics.
+//!
+//! While the lock is released, Rust code must not access OCaml runtime or
+//! OCaml heap. [`Blocking::run`] enforces this statically: the closure and its
+//! result must be `Send + 'static`, so they can't hold `ocaml::Value`s or data
+//! borrowed from OCaml values, such as `&str` of an OCaml string, and
+//! `ocaml::Runtime` handle is mutably borrowed meanwhile, so it can't be used
+//! to convert values or call OCaml functions.
+//! [`crate::domain_executor::ocaml_runtime`] panics on the thread, which has
+//! released the lock, as well. [`Blocking::enter`] can't enforce the former,
+//! hence it's `unsafe`.
+//!
+//! ```rust
+//! use ocaml_lwt_interop::blocking::Blocking;
+//!
+//! #[ocaml::func]
+//! pub fn my_checksum(data: String) -> i64 {
+//!     Blocking::run(gc, move || data.bytes().map(|b| b as i64).sum())
+//! }
+//! ```
+//!
+//! `#[ocaml_lwt_interop::blocking]` attribute does the same for the whole
+//! body of a stub, see the crate-level documentation.
+//!
+//! Arguments are converted from OCaml before the lock is released, and the
+//! result is converted to OCaml after the lock is re-acquired, so arguments of
+//! such stubs must be owned, e.g. `String` rather than `&str`. Off-heap
+//! buffers, such as [`crate::lwt_bytes::PinnedBuffer`], can be accessed
+//! safely.
+
+use std::marker::PhantomData;
+
+use crate::caml_runtime::ReleasedLockGuard;
+
+/// A scope, in which OCaml domain lock is released, see the
+/// [module-level documentation](self).
+pub struct Blocking<'gc> {
+    _guard: ReleasedLockGuard,
+    _gc: PhantomData<&'gc mut ocaml::Runtime>,
+}
+
+impl<'gc> Blocking<'gc> {
+    /// Releases OCaml domain lock until returned `Blocking` is dropped.
+    /// `gc` can not be used while `Blocking` is alive.
+    ///
+    /// # Safety
+    ///
+    /// Values pointing into OCaml heap, such as `ocaml::Value`, or `&str`
+    /// borrowed from OCaml string, must not be accessed while `Blocking` is
+    /// alive, as OCaml GC may move or collect them meanwhile. Prefer
+    /// [`Blocking::run`], which enforces this.
+    pub unsafe fn enter(gc: &'gc mut ocaml::Runtime) -> Self {
+        let _ = gc;
+        Self {
+            _guard: unsafe { ReleasedLockGuard::new() },
+            _gc: PhantomData,
+        }
+    }
+
+    /// Runs `f` with OCaml domain lock released, and returns its result.
+    pub fn run<F, R>(gc: &'gc mut ocaml::Runtime, f: F) -> R
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        // SAFETY: `f` and its result can't refer to OCaml heap, as they are
+        // `Send + 'static`
+        let _blocking = unsafe { Self::enter(gc) };
+        f()
+    }
+}
@@ -57,7 +57,9 @@
 //! [3] <https://ocaml.org/manual/5.2/parallelism.html>
 
 use std::{
+    cell::Cell,
     ffi::c_int,
+    marker::PhantomData,
     panic::{catch_unwind, UnwindSafe},
     process::abort,
 };
@@ -125,19 +127,58 @@ pub(crate) fn unregister_thread() {
     }
 }
 
+thread_local! {
+    /// Set while current thread has released OCaml domain lock via
+    /// [`ReleasedLockGuard`]
+    static LOCK_RELEASED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Returns `true` if current thread has released OCaml domain lock via
+/// [`ReleasedLockGuard`], and thus must not access OCaml runtime.
+pub(crate) fn is_lock_released() -> bool {
+    LOCK_RELEASED.with(|released| released.get())
+}
+
+/// Releases OCaml domain lock for the lifetime of the guard, the lock is
+/// re-acquired when the guard is dropped, including during unwinding.
+pub(crate) struct ReleasedLockGuard {
+    was_released: bool,
+    /* must be dropped on the same thread */
+    _not_send: PhantomData<*const ()>,
+}
+
+impl ReleasedLockGuard {
+    /// Releases OCaml domain lock.
+    ///
+    /// # Safety
+    ///
+    /// Current thread must hold OCaml domain lock. Nothing, that requires
+    /// the lock to be held, must be used while the guard is alive.
+    pub(crate) unsafe fn new() -> Self {
+        caml_release_runtime_system();
+        let was_released = LOCK_RELEASED.with(|released| released.replace(true));
+        Self {
+            was_released,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl Drop for ReleasedLockGuard {
+    fn drop(&mut self) {
+        unsafe { caml_acquire_runtime_system() };
+        LOCK_RELEASED.with(|released| released.set(self.was_released));
+    }
+}
+
 /// Runs `f` with OCaml domain lock being released. `f` **MUST NOT** use any
 /// functions that require OCaml domain lock to be held.
 pub(crate) fn with_released_lock<F, R>(f: F) -> R
 where
     F: FnOnce() -> R + UnwindSafe,
 {
-    unsafe { caml_release_runtime_system() };
-    let result = catch_unwind(f);
-    unsafe { caml_acquire_runtime_system() };
-    match result {
-        Ok(value) => value,
-        Err(panic) => std::panic::resume_unwind(panic),
-    }
+    let _guard = unsafe { ReleasedLockGuard::new() };
+    f()
 }
 
 /// Runs `f` with OCaml domain lock being acquired. `f` can safely use any
@@ -0,0 +1,195 @@
+//! Bounded channels which can be shared between Lwt and Rust tasks.
+//!
+//! # Overview
+//!
+//! [`Channel<T>`] is a multi-producer multi-consumer bounded channel built on
+//! top of [`async_channel`]. It is runtime-agnostic, so the same channel can be
+//! used from tasks running on the
+//! [OCaml domain executor](crate::domain_executor::DomainExecutor), from Tokio
+//! tasks, or from any other thread.
+//!
+//! Channels carrying OCaml values ([`MlChannel`]) are exposed to OCaml as
+//! `'a Rust_async.Channel.t`. OCaml side gets `send` and `recv` functions,
+//! returning Lwt promises, which are resolved by tasks running on the domain
+//! executor, i.e. OCaml waiters are woken up through the same machinery as
+//! any other [`crate::promise::Promise`] created from Rust.
+//!
+//! # Backpressure and closing
+//!
+//! `send` waits while the channel is full, `recv` waits while it is empty. Any
+//! side can close the channel: pending and future `send` calls fail with
+//! [`crate::error::Error::ChannelClosed`], while `recv` keeps returning
+//! buffered values and then returns `None`.
+
+use crate::domain_executor::ocaml_runtime;
+use ocaml_rs_smartptr::ml_box::MlBox;
+
+/// Sending half of a [`Channel`].
+#[derive(Debug)]
+pub struct Sender<T> {
+    inner: async_channel::Sender<T>,
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    /// Sends a value into the channel, waiting while the channel is full.
+    ///
+    /// Returns [`crate::error::Error::ChannelClosed`] if the channel is closed.
+    pub async fn send(&self, value: T) -> Result<(), crate::error::Error> {
+        self.inner
+            .send(value)
+            .await
+            .map_err(|_| crate::error::Error::ChannelClosed)
+    }
+
+    /// Closes the channel, returns `true` if this call has closed it.
+    pub fn close(&self) -> bool {
+        self.inner.close()
+    }
+
+    /// Returns `true` if the channel is closed.
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+}
+
+/// Receiving half of a [`Channel`].
+#[derive(Debug)]
+pub struct Receiver<T> {
+    inner: async_channel::Receiver<T>,
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Receives a value from the channel, waiting while the channel is empty.
+    ///
+    /// Returns `None` once the channel is closed and drained.
+    pub async fn recv(&self) -> Option<T> {
+        self.inner.recv().await.ok()
+    }
+
+    /// Closes the channel, returns `true` if this call has closed it.
+    pub fn close(&self) -> bool {
+        self.inner.close()
+    }
+
+    /// Returns `true` if the channel is closed.
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+}
+
+/// A bounded channel, holding both [`Sender`] and [`Receiver`] halves.
+///
+/// Cloning a `Channel` is cheap, all clones refer to the same underlying
+/// channel.
+#[derive(Debug)]
+pub struct Channel<T> {
+    sender: Sender<T>,
+    receiver: Receiver<T>,
+}
+
+impl<T> Clone for Channel<T> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            receiver: self.receiver.clone(),
+        }
+    }
+}
+
+impl<T> Channel<T> {
+    /// Creates a new channel which can buffer up to `capacity` values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn bounded(capacity: usize) -> Self {
+        let (sender, receiver) = async_channel::bounded(capacity);
+        Self {
+            sender: Sender { inner: sender },
+            receiver: Receiver { inner: receiver },
+        }
+    }
+
+    /// Returns a new sending half of this channel.
+    pub fn sender(&self) -> Sender<T> {
+        self.sender.clone()
+    }
+
+    /// Returns a new receiving half of this channel.
+    pub fn receiver(&self) -> Receiver<T> {
+        self.receiver.clone()
+    }
+
+    /// Sends a value into the channel, see [`Sender::send`].
+    pub async fn send(&self, value: T) -> Result<(), crate::error::Error> {
+        self.sender.send(value).await
+    }
+
+    /// Receives a value from the channel, see [`Receiver::recv`].
+    pub async fn recv(&self) -> Option<T> {
+        self.receiver.recv().await
+    }
+
+    /// Closes the channel, returns `true` if this call has closed it.
+    pub fn close(&self) -> bool {
+        self.sender.close()
+    }
+
+    /// Returns `true` if the channel is closed.
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+}
+
+/// A channel of OCaml values, this is what `'a Rust_async.Channel.t` wraps.
+///
+/// Values are kept in the channel as [`MlBox`]es, so they are safe to be
+/// buffered while OCaml GC is running. Typed helpers
+/// [`MlChannel::send_value`] and [`MlChannel::recv_value`] convert
+/// values to and from OCaml, and thus must only be used from tasks running on
+/// the OCaml domain executor. Tokio tasks can use them via
+/// [`crate::domain_executor::Handle::spawn`].
+#[derive(Clone, Debug)]
+pub struct MlChannel(pub Channel<MlBox>);
+
+impl MlChannel {
+    /// Creates a new channel which can buffer up to `capacity` values.
+    pub fn bounded(capacity: usize) -> Self {
+        Self(Channel::bounded(capacity))
+    }
+
+    /// Converts `value` to OCaml and sends it into the channel.
+    pub async fn send_value<T: ocaml::ToValue>(
+        &self,
+        value: T,
+    ) -> Result<(), crate::error::Error> {
+        let ml_box = {
+            let gc = ocaml_runtime();
+            MlBox::new(&gc, value.to_value(&gc))
+        };
+        self.0.send(ml_box).await
+    }
+
+    /// Receives a value from the channel and converts it from OCaml.
+    pub async fn recv_value<T: ocaml::FromValue>(&self) -> Option<T> {
+        let ml_box = self.0.recv().await?;
+        let gc = ocaml_runtime();
+        Some(T::from_value(ml_box.as_value(&gc)))
+    }
+}
@@ -0,0 +1,81 @@
+//! Limiting concurrency of async stubs.
+//!
+//! # Overview
+//!
+//! Each call of an `#[ocaml_lwt_interop::func]` stub spawns a task onto the
+//! [OCaml domain executor](crate::domain_executor::DomainExecutor), so a flood
+//! of calls from OCaml creates an unbounded number of tasks. The
+//! `max_concurrent` argument of the macro bounds the number of concurrently
+//! running bodies of a stub by a [`ConcurrencyLimit`], which is a static
+//! [`tokio::sync::Semaphore`] per stub:
+//!
+//! ```rust
+//! #[ocaml_lwt_interop::func(max_concurrent = 64)]
+//! pub fn my_handler(req: String) -> String {
+//!     req
+//! }
+//! ```
+//!
+//! The `overload` argument chooses what happens to excess calls:
+//!
+//! - `overload = "wait"` (default) - calls wait for a permit in FIFO order.
+//!   Waiting calls are not free: each of them is a spawned task, holding the
+//!   body of the stub along with its arguments, so memory still grows with
+//!   the number of calls, only the work done at once is bounded.
+//! - `overload = "reject"` - calls are rejected right away with
+//!   `Rust_async.Overloaded` exception, see [`overloaded`].
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::promise::Promise;
+
+// OCaml callbacks are registered in ../lib/Rust_async.ml
+ocaml::import! {
+    // `olwti_lwt_fail_overloaded` calls `Lwt.fail Overloaded`
+    fn olwti_lwt_fail_overloaded() -> ocaml::Value;
+}
+
+/// A limit of concurrently running bodies of a stub, see [module-level
+/// documentation](self).
+#[derive(Debug)]
+pub struct ConcurrencyLimit {
+    semaphore: Semaphore,
+}
+
+impl ConcurrencyLimit {
+    /// Creates a limit allowing `max_concurrent` bodies to run at once, can be
+    /// used in `static` items. `max_concurrent` must not exceed
+    /// [`Semaphore::MAX_PERMITS`].
+    pub const fn new(max_concurrent: usize) -> Self {
+        Self {
+            semaphore: Semaphore::const_new(max_concurrent),
+        }
+    }
+
+    /// Waits for a permit, permits are handed out in FIFO order. The permit is
+    /// returned back once dropped.
+    pub async fn acquire(&self) -> SemaphorePermit<'_> {
+        self.semaphore
+            .acquire()
+            .await
+            .expect("ConcurrencyLimit semaphore is never closed")
+    }
+
+    /// Takes a permit if one is available right away.
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        self.semaphore.try_acquire().ok()
+    }
+
+    /// Number of permits available right now.
+    pub fn available(&self) -> usize {
+        self.semaphore.available_permits()
+    }
+}
+
+/// Creates an already rejected promise via `Lwt.fail` with
+/// `Rust_async.Overloaded` exception.
+pub fn overloaded<T: ocaml::ToValue>(gc: &ocaml::Runtime) -> Promise<T> {
+    let v_fut = unsafe { olwti_lwt_fail_overloaded(gc) }
+        .expect("olwti_lwt_fail_overloaded has thrown an exception");
+    Promise::from_raw(gc, v_fut)
+}
@@ -8,7 +8,8 @@
 //! ensuring proper interaction with the OCaml runtime system.
 //!
 //! The executor leverages the [`async_executor`] crate to run async tasks and
-//! integrates with the [`tokio`] runtime for asynchronous I/O operations. It
+//! integrates with the [`tokio`] runtime for asynchronous I/O operations (if
+//! `tokio` feature is enabled, otherwise no extra threads are started). It
 //! provides mechanisms to spawn async tasks within the context of an OCaml
 //! domain, ensuring that the OCaml runtime system's domain lock is properly
 //! managed during the execution of these tasks.
@@ -30,30 +31,36 @@
 //! The `DomainExecutorDriver` struct drives the executor by polling its future
 //! and ensures that tasks are executed in the context of the OCaml domain.
 //!
-//! The module also provides functions to obtain the current Tokio runtime and
+//! The module also provides functions to obtain the current Tokio runtime
+//! (with `tokio` feature) or async-io runtime (with `async-io` feature), and
 //! to execute code within the OCaml domain lock.
 //!
 
 // Good read on async streams, executors, reactors and tasks:
 // https://www.qovery.com/blog/a-guided-tour-of-streams-in-rust
 
-use crate::{caml_runtime, notification::Notification};
+use crate::{
+    caml_runtime,
+    notification::Notification,
+    promise::SettlementBatch,
+    root_table,
+    tasks::{TaskInfo, TaskRegistry},
+};
 use std::{
     cell::RefCell,
     future::Future,
     marker::PhantomData,
-    panic::UnwindSafe,
+    panic::{Location, UnwindSafe},
     pin::Pin,
     rc::Rc,
     sync::{
-        atomic::{AtomicUsize, Ordering},
-        mpsc, Arc, Mutex, OnceLock, Weak,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc, Arc, Mutex,
     },
-    task::{Context, Waker},
+    task::{Context, Poll, Waker},
 };
 
 use async_executor::{Executor, Task};
-use tokio::runtime::Builder;
 
 use ocaml_rs_smartptr::ptr::DynBox;
 
@@ -73,7 +80,11 @@ use ocaml_rs_smartptr::ptr::DynBox;
 ///
 /// So it it safe to run OCaml code within Tokio tasks on this runtime, if OCaml
 /// domain lock is properly acquired.
+#[cfg(feature = "tokio")]
 fn global_tokio_runtime() -> Arc<tokio::runtime::Runtime> {
+    use std::sync::{atomic::AtomicUsize, OnceLock, Weak};
+    use tokio::runtime::Builder;
+
     static RT: OnceLock<Mutex<Weak<tokio::runtime::Runtime>>> = OnceLock::new();
     let mut weak_rt = RT.get_or_init(|| Mutex::new(Weak::new())).lock().unwrap();
     match weak_rt.upgrade() {
@@ -98,6 +109,149 @@ fn global_tokio_runtime() -> Arc<tokio::runtime::Runtime> {
     }
 }
 
+/// A thread, driving [`async_io`] reactor, and running its own executor,
+/// see [`async_io_rt`].
+///
+/// The thread is registered with the OCaml runtime system, the same way as
+/// worker threads of the global Tokio runtime are, so it is safe to run OCaml
+/// code within tasks, spawned on this executor, if OCaml domain lock is
+/// properly acquired.
+///
+/// The thread is stopped once the last reference to `AsyncIoRuntime` is
+/// dropped.
+#[cfg(feature = "async-io")]
+pub struct AsyncIoRuntime {
+    executor: Arc<Executor<'static>>,
+    /// Dropping the sender stops the driver thread.
+    _stop: async_channel::Sender<()>,
+}
+
+#[cfg(feature = "async-io")]
+impl AsyncIoRuntime {
+    /// Starts a new driver thread.
+    fn new() -> Self {
+        use std::sync::atomic::AtomicUsize;
+
+        static ATOMIC_ID: AtomicUsize = AtomicUsize::new(0);
+        let id = ATOMIC_ID.fetch_add(1, Ordering::SeqCst);
+        let executor = Arc::new(Executor::new());
+        let (stop, stopped) = async_channel::bounded::<()>(1);
+        let ex = executor.clone();
+        std::thread::Builder::new()
+            .name(format!("olwti-async-io-{}", id))
+            .spawn(move || {
+                caml_runtime::register_thread();
+                // `block_on` drives the reactor while waiting
+                async_io::block_on(ex.run(async move {
+                    let _ = stopped.recv().await;
+                }));
+                caml_runtime::unregister_thread();
+            })
+            .expect("failed to spawn async-io driver thread");
+        Self {
+            executor,
+            _stop: stop,
+        }
+    }
+
+    /// Spawns a new future onto the executor, running on the driver thread.
+    ///
+    /// The future must be `Send` and `'static`. Returns a `Task` that can be
+    /// used to await the result. Tasks must not block, as the same thread
+    /// drives the reactor.
+    pub fn spawn<T>(&self, future: impl Future<Output = T> + Send + 'static) -> Task<T>
+    where
+        T: Send + 'static,
+    {
+        self.executor.spawn(future)
+    }
+}
+
+/// Returns a reference to a global async-io runtime, i.e. a thread driving
+/// [`async_io`] reactor, see [`AsyncIoRuntime`]. Just like
+/// [`global_tokio_runtime`], "global" refers only to [`crate`] scope.
+#[cfg(feature = "async-io")]
+fn global_async_io_runtime() -> Arc<AsyncIoRuntime> {
+    use std::sync::{OnceLock, Weak};
+
+    static RT: OnceLock<Mutex<Weak<AsyncIoRuntime>>> = OnceLock::new();
+    let mut weak_rt = RT.get_or_init(|| Mutex::new(Weak::new())).lock().unwrap();
+    match weak_rt.upgrade() {
+        Some(rt) => rt,
+        None => {
+            let new_rt = Arc::new(AsyncIoRuntime::new());
+            *weak_rt = Arc::downgrade(&new_rt);
+            new_rt
+        }
+    }
+}
+
+/// Returns the epoll file descriptor of I/O driver of `runtime`.
+///
+/// Tokio does not expose it, so a private socket is registered with the
+/// driver, and the driver's epoll instance is the one, which has this socket
+/// among its targets in `/proc/self/fdinfo`. The socket is identified by both
+/// file descriptor and inode, and no other epoll instance can have it
+/// registered, so other threads creating or closing epoll instances meanwhile
+/// can't confuse the lookup. Fails on platforms other than Linux.
+#[cfg(feature = "tokio")]
+fn reactor_epoll_fd(runtime: &tokio::runtime::Runtime) -> std::io::Result<i32> {
+    use std::os::unix::{fs::MetadataExt, io::AsRawFd, net::UnixStream};
+
+    let (probe, _peer) = UnixStream::pair()?;
+    probe.set_nonblocking(true)?;
+    let probe_fd = probe.as_raw_fd();
+    let probe_ino = std::fs::metadata(format!("/proc/self/fd/{}", probe_fd))?.ino();
+    let probe = {
+        let _guard = runtime.enter();
+        tokio::net::UnixStream::from_std(probe)?
+    };
+    // Tokio keeps a duplicate of its epoll file descriptor, so candidates are
+    // told apart by the inode of the epoll instance they refer to
+    let found: Vec<(i32, u64)> = std::fs::read_dir("/proc/self/fd")?
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .filter_map(|fd: i32| {
+            // Descriptors might be closed meanwhile, which is not an error
+            let info =
+                std::fs::read_to_string(format!("/proc/self/fdinfo/{}", fd)).ok()?;
+            if !info
+                .lines()
+                .any(|line| epoll_target(line) == Some((probe_fd, probe_ino)))
+            {
+                return None;
+            }
+            let ino = std::fs::metadata(format!("/proc/self/fd/{}", fd))
+                .ok()?
+                .ino();
+            Some((fd, ino))
+        })
+        .collect();
+    drop(probe);
+    match found[..] {
+        [(fd, ino), ref rest @ ..] if rest.iter().all(|&(_, other)| other == ino) => {
+            Ok(fd)
+        }
+        _ => Err(std::io::Error::other(format!(
+            "can't identify epoll instance of Tokio I/O driver, {} candidates found",
+            found.len()
+        ))),
+    }
+}
+
+/// Parses a target line of epoll `fdinfo`, such as
+/// `tfd: 5 events: 19 data: 5 pos:0 ino:61d sdev:7`, returning the file
+/// descriptor and the inode of the target.
+#[cfg(feature = "tokio")]
+fn epoll_target(line: &str) -> Option<(i32, u64)> {
+    let mut fields = line.split_whitespace();
+    if fields.next()? != "tfd:" {
+        return None;
+    }
+    let fd = fields.next()?.parse().ok()?;
+    let ino = fields.find_map(|field| field.strip_prefix("ino:"))?;
+    Some((fd, u64::from_str_radix(ino, 16).ok()?))
+}
+
 // OCaml callbacks are registered in ../lib/Rust_async.ml
 ocaml::import! {
     // `olwti_current_executor` returns the current domain's executor. This
@@ -106,6 +260,50 @@ ocaml::import! {
     // chain. It is guaranteed that a single executor is associated to any given
     // OCaml domain.
     fn olwti_current_executor() -> DynBox<DomainExecutor>;
+    // `olwti_lwt_pause` calls `Lwt.pause`
+    fn olwti_lwt_pause() -> crate::promise::Promise<()>;
+}
+
+/// State shared between [`DomainExecutor`] and the waker of its driver.
+///
+/// By default, each wake-up of the executor sends a notification to Lwt event
+/// loop. In iteration hooks mode, the executor is ticked from
+/// `Lwt_main.Enter_iter_hooks` and `Leave_iter_hooks` instead, and the
+/// notification is only sent while Lwt event loop might be blocked waiting
+/// for I/O.
+struct WakeState {
+    notification: Notification,
+    /// Set by the waker, cleared once the driver is ticked.
+    pending: AtomicBool,
+    /// Whether the waker has to send the notification.
+    blocked: AtomicBool,
+    /// Number of notifications sent so far.
+    notifications_sent: AtomicU64,
+}
+
+impl WakeState {
+    fn new(notification: Notification) -> Self {
+        Self {
+            notification,
+            pending: AtomicBool::new(false),
+            blocked: AtomicBool::new(true),
+            notifications_sent: AtomicU64::new(0),
+        }
+    }
+
+    fn notify(&self) {
+        self.notifications_sent.fetch_add(1, Ordering::Relaxed);
+        self.notification.send();
+    }
+
+    fn wake(&self) {
+        // Pairs with `DomainExecutor::enter_iter`: either the waker observes
+        // `blocked`, or `enter_iter` observes `pending`
+        self.pending.store(true, Ordering::SeqCst);
+        if self.blocked.load(Ordering::SeqCst) {
+            self.notify();
+        }
+    }
 }
 
 /// A driver for the `DomainExecutor`, responsible for running the executor's
@@ -119,20 +317,25 @@ ocaml::import! {
 pub struct DomainExecutorDriver {
     fut: Pin<Box<dyn Future<Output = ()> + Sync + Send + 'static>>,
     waker: Waker,
+    wake_state: Arc<WakeState>,
 }
 
 impl DomainExecutorDriver {
     /// Creates a new `DomainExecutorDriver` for the given executor.
     ///
-    /// The `notification` is used to create a waker that notifies Lwt event
+    /// The `wake_state` is used to create a waker that notifies Lwt event
     /// loop when new tasks are available.
-    fn new(ex: Arc<Executor<'static>>, notification: Notification) -> Self {
-        let waker = waker_fn::waker_fn(move || notification.send());
+    fn new(ex: Arc<Executor<'static>>, wake_state: Arc<WakeState>) -> Self {
+        let waker = {
+            let wake_state = wake_state.clone();
+            waker_fn::waker_fn(move || wake_state.wake())
+        };
         Self {
             fut: Box::pin(async move {
                 ex.run(futures_lite::future::pending::<()>()).await;
             }),
             waker,
+            wake_state,
         }
     }
 
@@ -144,6 +347,7 @@ impl DomainExecutorDriver {
     /// tasks are available. Some care needs to be taken so as not to starve the
     /// OCaml event loop if large number of tasks are getting spawned/executed.
     pub fn tick(&mut self) {
+        self.wake_state.pending.store(false, Ordering::SeqCst);
         let mut cx = Context::from_waker(&self.waker);
         let _ = self.fut.as_mut().poll(&mut cx);
     }
@@ -152,17 +356,36 @@ impl DomainExecutorDriver {
 /// Context for the `DomainExecutor`, stored in a thread-local stack to manage
 /// executor instances.
 ///
-/// The `DomainExecutorContext` holds a reference to the executor and allows the
-/// executor to be accessed within the current thread.
+/// The `DomainExecutorContext` holds a reference to the executor and its task
+/// list, and allows the executor to be accessed within the current thread.
 #[derive(Clone)]
 struct DomainExecutorContext {
     executor: Arc<Executor<'static>>,
+    tasks: Arc<TaskRegistry>,
 }
 
 impl DomainExecutorContext {
-    /// Creates a new `DomainExecutorContext` with the given executor.
-    fn new(executor: Arc<Executor<'static>>) -> Self {
-        Self { executor }
+    /// Creates a new `DomainExecutorContext` for the given executor.
+    fn new(domain_executor: &DomainExecutor) -> Self {
+        Self {
+            executor: domain_executor.executor.clone(),
+            tasks: domain_executor.tasks.clone(),
+        }
+    }
+
+    /// Spawns a future onto the executor, registering it in the task list
+    /// with the caller location.
+    #[track_caller]
+    fn spawn<T>(
+        &self,
+        name: Option<&'static str>,
+        future: impl Future<Output = T> + Send + 'static,
+    ) -> Task<T>
+    where
+        T: Send + 'static,
+    {
+        let future = self.tasks.track(name, Location::caller(), future);
+        self.executor.spawn(future)
     }
 }
 
@@ -214,7 +437,18 @@ pub struct DomainExecutor {
     /// The driver that runs the executor.
     pub driver: Mutex<DomainExecutorDriver>,
     /// The Tokio runtime used for asynchronous I/O.
+    #[cfg(feature = "tokio")]
     pub runtime: Arc<tokio::runtime::Runtime>,
+    /// The thread driving [`async_io`] reactor.
+    #[cfg(feature = "async-io")]
+    pub async_io: Arc<AsyncIoRuntime>,
+    /// Epoll file descriptor of the `current_thread` Tokio runtime I/O driver
+    /// in single-threaded mode.
+    #[cfg(feature = "tokio")]
+    reactor_fd: Option<i32>,
+    wake_state: Arc<WakeState>,
+    /// Live tasks, see [`crate::tasks`].
+    tasks: Arc<TaskRegistry>,
 }
 
 impl DomainExecutor {
@@ -224,16 +458,124 @@ impl DomainExecutor {
     /// tasks are available.
     pub fn new(notification: Notification) -> DomainExecutor {
         let executor = Arc::new(Executor::new());
-        let driver =
-            Mutex::new(DomainExecutorDriver::new(executor.clone(), notification));
-        let runtime = global_tokio_runtime();
+        let wake_state = Arc::new(WakeState::new(notification));
+        let driver = Mutex::new(DomainExecutorDriver::new(
+            executor.clone(),
+            wake_state.clone(),
+        ));
         DomainExecutor {
             executor,
             driver,
+            #[cfg(feature = "tokio")]
+            runtime: global_tokio_runtime(),
+            #[cfg(feature = "async-io")]
+            async_io: global_async_io_runtime(),
+            #[cfg(feature = "tokio")]
+            reactor_fd: None,
+            wake_state,
+            tasks: Arc::default(),
+        }
+    }
+
+    /// Creates a new `DomainExecutor` in single-threaded mode.
+    ///
+    /// Instead of the global multi-threaded Tokio runtime, the executor gets
+    /// its own `current_thread` Tokio runtime, which is turned by
+    /// [`DomainExecutor::tick`], so that Rust I/O is performed on OCaml domain
+    /// thread, and there are no cross-thread wake-ups. Lwt event loop has to
+    /// call `tick` once [`DomainExecutor::reactor_fd`] becomes readable, and
+    /// periodically to fire expired Tokio timers: Tokio does not expose the
+    /// deadline of its next timer, so the period bounds timer precision, and
+    /// the process wakes up with that period even when idle.
+    ///
+    /// Tokio does not expose the epoll instance of its I/O driver either, so
+    /// it's looked up in `/proc/self/fdinfo` by a socket registered with the
+    /// driver for this purpose. Fails on platforms other than Linux.
+    ///
+    /// Without `tokio` feature, this is the same as [`DomainExecutor::new`].
+    pub fn new_single_threaded(
+        notification: Notification,
+    ) -> std::io::Result<DomainExecutor> {
+        let executor = Arc::new(Executor::new());
+        let wake_state = Arc::new(WakeState::new(notification));
+        let driver = Mutex::new(DomainExecutorDriver::new(
+            executor.clone(),
+            wake_state.clone(),
+        ));
+        #[cfg(feature = "tokio")]
+        let (runtime, reactor_fd) = {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?;
+            let reactor_fd = reactor_epoll_fd(&runtime)?;
+            (Arc::new(runtime), Some(reactor_fd))
+        };
+        Ok(DomainExecutor {
+            executor,
+            driver,
+            #[cfg(feature = "tokio")]
             runtime,
+            #[cfg(feature = "async-io")]
+            async_io: global_async_io_runtime(),
+            #[cfg(feature = "tokio")]
+            reactor_fd,
+            wake_state,
+            tasks: Arc::default(),
+        })
+    }
+
+    /// Returns the file descriptor, which becomes readable once Tokio I/O
+    /// driver of the executor in single-threaded mode has events to process.
+    ///
+    /// Returns `None` for executors not in single-threaded mode.
+    pub fn reactor_fd(&self) -> Option<i32> {
+        #[cfg(feature = "tokio")]
+        let reactor_fd = self.reactor_fd;
+        #[cfg(not(feature = "tokio"))]
+        let reactor_fd = None;
+        reactor_fd
+    }
+
+    /// Switches iteration hooks mode on or off.
+    ///
+    /// In iteration hooks mode, Lwt event loop has to call
+    /// [`DomainExecutor::enter_iter`] from `Lwt_main.Enter_iter_hooks` and
+    /// [`DomainExecutor::leave_iter`] from `Lwt_main.Leave_iter_hooks`, and
+    /// wake-ups of the executor only send the notification while the event
+    /// loop might be blocked in between. This avoids most of notification
+    /// pipe writes when tasks are woken up from OCaml domain thread itself.
+    pub fn set_iteration_hooks(&self, enabled: bool) {
+        self.wake_state.blocked.store(!enabled, Ordering::SeqCst);
+    }
+
+    /// Runs pending tasks before Lwt event loop iteration, and marks the event
+    /// loop as possibly blocked, see [`DomainExecutor::set_iteration_hooks`].
+    pub fn enter_iter(&self) {
+        if self.wake_state.pending.load(Ordering::SeqCst) {
+            self.tick();
+        }
+        self.wake_state.blocked.store(true, Ordering::SeqCst);
+        // Tasks might have been woken up from other threads while `blocked`
+        // was not set yet
+        if self.wake_state.pending.load(Ordering::SeqCst) {
+            self.wake_state.notify();
+        }
+    }
+
+    /// Marks Lwt event loop as running after its iteration, and runs pending
+    /// tasks, see [`DomainExecutor::set_iteration_hooks`].
+    pub fn leave_iter(&self) {
+        self.wake_state.blocked.store(false, Ordering::SeqCst);
+        if self.wake_state.pending.load(Ordering::SeqCst) {
+            self.tick();
         }
     }
 
+    /// Returns the number of notifications sent to Lwt event loop so far.
+    pub fn notifications_sent(&self) -> u64 {
+        self.wake_state.notifications_sent.load(Ordering::Relaxed)
+    }
+
     /// Ticks the executor, driving task execution.
     ///
     /// This method should be called whenever the notification fires to ensure
@@ -241,32 +583,123 @@ impl DomainExecutor {
     /// It acquires the executor's driver, enters the Tokio runtime context, enters the executor context,
     /// and then ticks the driver. This ensures that futures being polled have
     /// access to Tokio contenxt and Domain executor context, and are free to
-    /// use corresponding API calls, like [`tokio::spawn`] or
-    /// [`crate::domain_executor::spawn`].
+    /// use corresponding API calls, like `tokio::spawn` or
+    /// [`crate::domain_executor::spawn`]. Tokio runtime context is only
+    /// entered if `tokio` feature is enabled.
+    ///
+    /// Promises settled by tasks during the tick (see
+    /// [`crate::promise::Resolver`]) are passed to OCaml in one call at the
+    /// end of the tick.
+    ///
+    /// In single-threaded mode, the `current_thread` Tokio runtime processes
+    /// pending I/O events and expired timers and runs its tasks first, without
+    /// blocking. Tokio tasks spawned or woken up by tasks of the executor run
+    /// at the end of the same tick, up to Tokio's scheduling budget, rather
+    /// than waiting for the next I/O event or timer period.
     pub fn tick(&self) {
         let mut bridge = self.driver.lock().unwrap();
+        #[cfg(feature = "tokio")]
+        if self.runtime.handle().runtime_flavor()
+            == tokio::runtime::RuntimeFlavor::CurrentThread
+        {
+            self.runtime.block_on(async {
+                // Yielding makes `block_on` poll the I/O driver with zero
+                // timeout, instead of parking the thread
+                tokio::task::yield_now().await;
+                {
+                    let _self_guard = self.enter();
+                    let _settlements = SettlementBatch::begin();
+                    bridge.tick();
+                    root_table::reclaim(&ocaml_runtime());
+                }
+                // Lets Tokio run tasks spawned or woken up during the tick
+                tokio::task::yield_now().await;
+            });
+            return;
+        }
+        #[cfg(feature = "tokio")]
         let _guard = self.runtime.enter();
         let _self_guard = self.enter();
+        let _settlements = SettlementBatch::begin();
         bridge.tick();
+        root_table::reclaim(&ocaml_runtime());
     }
 
     /// Spawns a new future onto the executor.
     ///
     /// The future must be `Send` and `'static`. Returns a `Task` that can be
-    /// used to await the result.
+    /// used to await the result. The task is tracked with the caller location,
+    /// see [`crate::tasks`].
+    #[track_caller]
     pub fn spawn<T>(&self, future: impl Future<Output = T> + Send + 'static) -> Task<T>
     where
         T: Send + 'static,
     {
+        let future = self.tasks.track(None, Location::caller(), future);
+        self.executor.spawn(future)
+    }
+
+    /// Spawns a new future onto the executor, same as
+    /// [`DomainExecutor::spawn`], naming the task `name` in the task list.
+    #[track_caller]
+    pub fn spawn_named<T>(
+        &self,
+        name: &'static str,
+        future: impl Future<Output = T> + Send + 'static,
+    ) -> Task<T>
+    where
+        T: Send + 'static,
+    {
+        let future = self.tasks.track(Some(name), Location::caller(), future);
         self.executor.spawn(future)
     }
 
+    /// Returns a snapshot of tasks, which are spawned onto the executor and
+    /// are not completed yet, oldest first, see [`crate::tasks`].
+    pub fn tasks(&self) -> Vec<TaskInfo> {
+        self.tasks.snapshot()
+    }
+
+    /// Switches tracking of tasks on or off, see [`crate::tasks`]. Only tasks
+    /// spawned while tracking is enabled are listed by
+    /// [`DomainExecutor::tasks`].
+    pub fn set_task_tracking(&self, enabled: bool) {
+        self.tasks.set_enabled(enabled);
+    }
+
+    /// Formats [`DomainExecutor::tasks`] as text, one task per line.
+    pub fn dump_tasks(&self) -> String {
+        let tasks = self.tasks();
+        let mut dump = format!("{} live tasks\n", tasks.len());
+        if !self.tasks.is_enabled() {
+            dump.push_str("(task tracking is disabled)\n");
+        }
+        for task in tasks {
+            dump.push_str(&task.to_string());
+            dump.push('\n');
+        }
+        dump
+    }
+
+    /// Polls `future` once in place, with the same contexts entered as in
+    /// [`DomainExecutor::tick`], and a no-op waker.
+    ///
+    /// Used to complete futures, which do not suspend, without spawning a
+    /// task. If `future` is pending, it's up to the caller to spawn it (the
+    /// executor polls it again, registering a real waker).
+    pub fn poll_once<F: Future + ?Sized>(&self, future: Pin<&mut F>) -> Poll<F::Output> {
+        #[cfg(feature = "tokio")]
+        let _guard = self.runtime.enter();
+        let _self_guard = self.enter();
+        future.poll(&mut Context::from_waker(Waker::noop()))
+    }
+
     /// Enters the executor context, pushing it onto the thread-local stack.
     ///
     /// Returns an `ExecutorGuard` that will pop the context off the stack when
     /// dropped.
     pub fn enter(&self) -> ExecutorGuard {
-        let executor_context = Rc::new(DomainExecutorContext::new(self.executor.clone()));
+        let executor_context = Rc::new(DomainExecutorContext::new(self));
         EXECUTOR_STACK.with(|stack| {
             stack.borrow_mut().push(executor_context.clone());
         });
@@ -288,6 +721,7 @@ impl DomainExecutor {
 /// # Panics
 ///
 /// Panics if there is no executor context registered in the current thread.
+#[track_caller]
 pub fn spawn<T>(future: impl Future<Output = T> + Send + 'static) -> Task<T>
 where
     T: Send + 'static,
@@ -295,7 +729,27 @@ where
     let ctx = DomainExecutor::current().expect(
         "There is no ocaml-lwt-interop executor context registered for current thread!",
     );
-    ctx.executor.spawn(future)
+    ctx.spawn(None, future)
+}
+
+/// Yields to Lwt event loop, i.e. the task continues only after Lwt event loop
+/// has run an iteration, including polling for I/O.
+///
+/// Unlike `futures_lite::future::yield_now()`, which only reschedules the task
+/// within the executor (a single [`DomainExecutor::tick`] may pick it straight
+/// back up), this is implemented via `Lwt.pause`, so long-running loops in
+/// Rust tasks can use it to let Lwt tasks make progress.
+///
+/// # Panics
+///
+/// Panics if called outside of a task running on OCaml domain executor, see
+/// [`ocaml_runtime`].
+pub async fn yield_to_lwt() {
+    let promise = {
+        let gc = &ocaml_runtime();
+        unsafe { olwti_lwt_pause(gc) }.expect("olwti_lwt_pause has thrown an exception")
+    };
+    promise.await.expect("Lwt.pause promise has been rejected")
 }
 
 /// Returns a reference to the global Tokio runtime.
@@ -304,10 +758,29 @@ where
 /// safe to call [`crate::domain_executor::run_in_ocaml_domain`] in tasks,
 /// spawned on this Tokio runtime, as worker threads are registered to OCaml
 /// runtime.
+///
+/// Only available with `tokio` feature, which is enabled by default.
+#[cfg(feature = "tokio")]
 pub fn tokio_rt() -> Arc<tokio::runtime::Runtime> {
     global_tokio_runtime()
 }
 
+/// Returns a reference to the global async-io runtime, see
+/// [`AsyncIoRuntime`].
+///
+/// Futures using [`async_io`] (or `smol`) timers and sockets can be awaited
+/// from any executor, including OCaml domain executor, as long as the
+/// reactor is driven. This runtime is started along with any
+/// [`DomainExecutor`], and it is safe to call
+/// [`crate::domain_executor::run_in_ocaml_domain`] in tasks, spawned on it, as
+/// its thread is registered to OCaml runtime.
+///
+/// Only available with `async-io` feature.
+#[cfg(feature = "async-io")]
+pub fn async_io_rt() -> Arc<AsyncIoRuntime> {
+    global_async_io_runtime()
+}
+
 /// A guard that provides access to the OCaml runtime handle within the current
 /// thread.
 ///
@@ -337,13 +810,19 @@ impl<'a> std::ops::Deref for OcamlRuntimeGuard<'a> {
 ///
 /// # Panics
 ///
-/// Panics if there is no executor context registered in the current thread.
+/// Panics if there is no executor context registered in the current thread,
+/// or if OCaml domain lock is released by the current thread, see
+/// [`crate::blocking::Blocking`].
 pub fn ocaml_runtime<'a>() -> OcamlRuntimeGuard<'a> {
     /* Ensure we're running in a task which is driven by our executor (which is
      * in turn `tick`-ed only from the same OCaml domain) */
     let _ctx = DomainExecutor::current().expect(
         "Can't obtain OCaml runtime handle when running outside of ocaml-lwt-interop executor context!",
     );
+    assert!(
+        !caml_runtime::is_lock_released(),
+        "Can't obtain OCaml runtime handle while OCaml domain lock is released!"
+    );
     OcamlRuntimeGuard {
         _marker: PhantomData,
         _marker2: PhantomData,
@@ -354,6 +833,7 @@ pub fn ocaml_runtime<'a>() -> OcamlRuntimeGuard<'a> {
 ///
 /// This function is useful if you have synchronous stub function that needs to
 /// start some background computation.
+#[track_caller]
 pub fn spawn_with_runtime<T>(
     gc: &ocaml::Runtime,
     future: impl Future<Output = T> + Send + 'static,
@@ -366,11 +846,42 @@ where
     ex.coerce().spawn(future)
 }
 
+/// Spawns a future onto the executor obtained from the OCaml runtime, naming
+/// the task `name` in the task list, see [`DomainExecutor::spawn_named`].
+///
+/// `#[ocaml_lwt_interop::func]` stubs spawn their bodies via this function,
+/// with the stub function name.
+#[track_caller]
+pub fn spawn_named_with_runtime<T>(
+    gc: &ocaml::Runtime,
+    name: &'static str,
+    future: impl Future<Output = T> + Send + 'static,
+) -> Task<T>
+where
+    T: Send + 'static,
+{
+    let ex = unsafe { olwti_current_executor(gc) }
+        .expect("olwti_current_executor has thrown an exception");
+    ex.coerce().spawn_named(name, future)
+}
+
+/// Polls `future` once with the executor obtained from the OCaml runtime, see
+/// [`DomainExecutor::poll_once`].
+pub fn poll_once_with_runtime<F: Future + ?Sized>(
+    gc: &ocaml::Runtime,
+    future: Pin<&mut F>,
+) -> Poll<F::Output> {
+    let ex = unsafe { olwti_current_executor(gc) }
+        .expect("olwti_current_executor has thrown an exception");
+    ex.coerce().poll_once(future)
+}
+
 /// Spawns a future onto the executor obtained from the OCaml runtime and
 /// returns an [`crate::promise::Promise`].
 ///
 /// The future's output must implement [`ocaml::ToValue`], allowing the result
 /// to be converted and resolved back into OCaml.
+#[track_caller]
 pub fn spawn_lwt<T>(
     gc: &ocaml::Runtime,
     fut: impl Future<Output = T> + Send + 'static,
@@ -415,11 +926,12 @@ impl Handle {
     ///
     /// The future must be `Send` and `'static`. Returns a `Task` that can be
     /// used to await the result.
+    #[track_caller]
     pub fn spawn<T>(&self, future: impl Future<Output = T> + Send + 'static) -> Task<T>
     where
         T: Send + 'static,
     {
-        self.ctx.executor.spawn(future)
+        self.ctx.spawn(None, future)
     }
 }
 
@@ -443,7 +955,7 @@ pub fn handle() -> Handle {
 pub fn handle_from_runtime(gc: &ocaml::Runtime) -> Handle {
     let domain_executor = unsafe { olwti_current_executor(gc) }
         .expect("olwti_current_executor has thrown an exception");
-    let ctx = DomainExecutorContext::new(domain_executor.coerce().executor.clone());
+    let ctx = DomainExecutorContext::new(domain_executor.coerce());
     Handle::new(ctx)
 }
 
@@ -468,11 +980,12 @@ pub fn handle_from_runtime(gc: &ocaml::Runtime) -> Handle {
 ///
 /// This is only safe to call on a thread that is registered with OCaml runtime,
 /// i.e. from a Tokio task, spawned using global Tokio runtime obtained via
-/// [`tokio_rt`].
+/// `tokio_rt`, or from a task spawned on `async_io_rt`.
 ///
 /// # Panics
 ///
 /// Panics if any of the synchronization primitives fail.
+#[track_caller]
 pub unsafe fn run_in_ocaml_domain<T: Send>(
     handle: &Handle,
     f: impl FnOnce(&ocaml::Runtime) -> T + UnwindSafe,
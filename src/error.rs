@@ -4,4 +4,14 @@ use thiserror::Error;
 pub enum Error {
     #[error("LWT promise was rejected with exception: {0}")]
     LwtPromiseRejection(String),
+    #[error("Channel is closed")]
+    ChannelClosed,
+    #[error("Operation has timed out")]
+    Timeout,
+}
+
+impl From<Error> for String {
+    fn from(err: Error) -> String {
+        err.to_string()
+    }
 }
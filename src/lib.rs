@@ -20,7 +20,21 @@
 //!   the OCaml runtime.
 //! - **Async Function Wrappers**: Provides wrappers for OCaml functions that
 //!   return Lwt promises, allowing them to be called from Rust and awaited
-//!   asynchronously.
+//!   asynchronously, and for Rust async closures, allowing them to be passed
+//!   to OCaml as functions returning Lwt promises.
+//! - **Channels**: Bounded channels which can be shared between Lwt and Rust
+//!   tasks, with backpressure and closing working in both directions.
+//! - **I/O Bridging**: Adapters between Tokio's `AsyncRead`/`AsyncWrite` and
+//!   `Lwt_io` channels, working in both directions.
+//! - **Zero-copy Buffers**: `Lwt_bytes.t` can be passed to and returned from
+//!   async stubs without copying, and accessed from Rust without OCaml domain
+//!   lock.
+//! - **Synchronization Primitives**: Mutex, semaphore and notification
+//!   primitives, which can be awaited from both OCaml and Rust.
+//! - **Blocking Sections**: Running synchronous Rust code with OCaml domain
+//!   lock released, letting other OCaml threads run meanwhile.
+//! - **Signals**: Unix signals received on Tokio runtime are forwarded to Lwt
+//!   promises, regardless of the thread, which has caught the signal.
 //!                                                                                                                                                                                           
 //! # `#[ocaml_lwt_interop::func]` Macro
 //!
@@ -77,6 +91,193 @@
 //! }
 //! ```
 //!
+//! Additionally, the macro polls the body once right in the stub (see
+//! [`domain_executor::poll_once_with_runtime`]). If the body completes without
+//! suspending, the stub returns already resolved (or rejected) promise, such as
+//! [`promise::Promise::resolved`], and no task is spawned at all.
+//!
+//! ## Result-returning functions
+//!
+//! By default, a function returning `Result<T, E>` resolves to
+//! `('a, 'e) result Lwt.t`, i.e. `('a, 'e) Lwt_result.t`. The `err` argument
+//! of the macro allows rejecting the promise with an exception instead:
+//!
+//! - `#[ocaml_lwt_interop::func(err = "raise")]` rejects the promise with
+//!   `Failure`, carrying the message of `Err` value, `E` must implement
+//!   `Display`.
+//! - `#[ocaml_lwt_interop::func(err = "exn")]` rejects the promise with `Err`
+//!   value, `E` must implement `ToValue` producing an OCaml exception.
+//!
+//! ```rust
+//! #[ocaml_lwt_interop::func(err = "raise")]
+//! pub fn my_parse(s: String) -> Result<i64, std::num::ParseIntError> {
+//!     s.parse()
+//! }
+//! ```
+//!
+//! Can be declared from OCaml side as follows:
+//!
+//! ```ocaml
+//! external my_parse : string -> int64 Lwt.t = "my_parse"
+//! ```
+//!
+//! In the other direction, [`promise::LwtResult`] and
+//! [`async_func::OCamlAsyncFunc::call_result`] allow awaiting
+//! `('a, 'e) Lwt_result.t` from Rust as `Result<T, E>`.
+//!
+//! ## Running on Tokio runtime
+//!
+//! By default, the body of the function runs on OCaml domain executor, i.e.
+//! on OCaml domain thread, so CPU-heavy or blocking bodies stall Lwt. The
+//! `tokio` argument runs the body on Tokio runtime via `tokio_rt().spawn`,
+//! and the `blocking` argument runs it on Tokio blocking thread pool via
+//! `spawn_blocking`, in which case the body is a regular, non-async function.
+//! Only argument conversion and promise resolution happen on OCaml domain
+//! executor. Both arguments require `tokio` feature.
+//!
+//! Such bodies must not access OCaml runtime:
+//! [`domain_executor::ocaml_runtime`] panics outside of OCaml domain executor.
+//! As a best-effort lint, the macro also rejects calls of `ocaml_runtime()`
+//! written directly in the body, but calls made indirectly, e.g. by helper
+//! functions, are only caught at run time. Awaiting OCaml promises or calling
+//! OCaml functions requires going back to OCaml domain executor via
+//! [`domain_executor::Handle`].
+//!
+//! ```rust
+//! #[ocaml_lwt_interop::func(blocking, err = "raise")]
+//! pub fn my_read_file(path: String) -> Result<String, std::io::Error> {
+//!     std::fs::read_to_string(path)
+//! }
+//! ```
+//!
+//! ## Limiting concurrency
+//!
+//! Each call of the stub spawns a task, so a flood of calls from OCaml creates
+//! as many tasks. The `max_concurrent` argument bounds the number of bodies of
+//! the stub running at once. Excess calls wait for their turn in FIFO order,
+//! or, with `overload = "reject"`, fail right away with
+//! `Rust_async.Overloaded` exception. See [`concurrency`] for details.
+//!
+//! ```rust
+//! #[ocaml_lwt_interop::func(max_concurrent = 64, overload = "reject")]
+//! pub fn my_handler(req: String) -> String {
+//!     req
+//! }
+//! ```
+//!
+//! ## Debugging hung promises
+//!
+//! Once enabled by `Rust_async.Debug.set_task_tracking true`, tasks spawned by
+//! the stubs are tracked by the executor until they complete, along with the
+//! stub name, spawn location, age and poll count.
+//! `Rust_async.Debug.dump_tasks ()` (or
+//! [`domain_executor::DomainExecutor::dump_tasks`] on Rust side) lists them,
+//! which shows the stubs behind promises that never resolve. Tracking is
+//! disabled by default to keep spawning cheap. See [`tasks`].
+//!
+//! # `#[ocaml_lwt_interop::blocking]` Macro
+//!
+//! This macro turns a synchronous function into a stub, which runs its body
+//! with OCaml domain lock released via [`blocking::Blocking`], so that other
+//! OCaml threads and `Lwt_preemptive` workers can run meanwhile. Arguments are
+//! converted before the lock is released, the result is converted after it is
+//! re-acquired. Arguments and the result must be `Send + 'static`, e.g.
+//! `String` rather than `&str`, so that the body can't access OCaml heap. Like
+//! `func`, the macro adds `#[ocaml::func]` automatically.
+//!
+//! ```rust
+//! #[ocaml_lwt_interop::blocking]
+//! pub fn my_reverse(data: String) -> String {
+//!     data.chars().rev().collect()
+//! }
+//! ```
+//!
+//! Can be declared from OCaml side as follows, and called e.g. via
+//! `Lwt_preemptive.detach`:
+//!
+//! ```ocaml
+//! external my_reverse : string -> string = "my_reverse"
+//! ```
+//!
+//! # `#[ocaml_lwt_interop::methods]` Macro
+//!
+//! This macro exposes `async fn` methods of a Rust type, which is handed to
+//! OCaml via `DynBox`, as async stubs. For each `async fn` taking `&self` it
+//! generates a stub named `<snake_case_type>_<method>`, which takes
+//! `DynBox<Self>` as the first argument. The `DynBox` is moved into the
+//! spawned future, so the object is kept alive until the method completes.
+//! Other items of the impl block are left as is.
+//!
+//! Stubs are declared for `ocaml_gen` in an OCaml module named after the type,
+//! along with the type itself as `t`. Module name and stub name prefix can be
+//! overridden with `module = "..."` and `prefix = "..."` arguments. The type
+//! still has to be registered via `register_rtti!`.
+//!
+//! ```rust
+//! use std::sync::Mutex;
+//!
+//! pub struct Cache {
+//!     values: Mutex<Vec<String>>,
+//! }
+//!
+//! #[ocaml_lwt_interop::methods]
+//! impl Cache {
+//!     pub async fn get(&self, idx: i64) -> Option<String> {
+//!         self.values.lock().unwrap().get(idx as usize).cloned()
+//!     }
+//! }
+//! ```
+//!
+//! Generated OCaml module looks as follows:
+//!
+//! ```ocaml
+//! module Cache = struct
+//!   type t = ...
+//!   external get : _ t' -> int64 -> string option Lwt.t = "cache_get"
+//! end
+//! ```
+//!
+//! # `#[ocaml_lwt_interop::ocaml_trait]` Macro
+//!
+//! This macro allows implementing a Rust trait with `async fn` methods in
+//! OCaml. Applied to a trait definition, it generates a struct named
+//! `OCaml<Trait>` (can be overridden with `name = "..."`), which implements
+//! the trait and can be converted from an OCaml record of functions returning
+//! `Lwt.t`. Record fields must follow the order of methods in the trait.
+//!
+//! Calls are routed onto the OCaml domain executor, which the record has been
+//! received on, so the trait object can be used from any thread, including
+//! Tokio tasks. Methods must return `Result<T, E>`, and OCaml functions
+//! return `('a, 'e) Lwt_result.t`. Rejections are converted into `E`, which
+//! must implement `From<ocaml_lwt_interop::error::Error>`, so that a failing
+//! OCaml implementation never brings the process down. Arguments and return
+//! values must be `Send`.
+//!
+//! Passing `ocaml_type = "..."` implements `OCamlDesc` for generated struct,
+//! so that it can be used in `ocaml_gen` stub declarations.
+//!
+//! ```rust
+//! #[ocaml_lwt_interop::ocaml_trait(ocaml_type = "Backend.t")]
+//! pub trait Backend {
+//!     async fn fetch(&self, key: String) -> Result<String, String>;
+//!     async fn store(&self, key: String, value: String) -> Result<(), String>;
+//! }
+//!
+//! #[ocaml_lwt_interop::func]
+//! pub fn my_lookup(backend: OCamlBackend, key: String) -> Result<String, String> {
+//!     backend.fetch(key).await
+//! }
+//! ```
+//!
+//! Corresponding OCaml record type:
+//!
+//! ```ocaml
+//! type t =
+//!   { fetch : string -> (string, string) Lwt_result.t
+//!   ; store : string -> string -> (unit, string) Lwt_result.t
+//!   }
+//! ```
+//!
 //! # Tokio integration
 //!
 //! `ocaml-lwt-interop` integrates Tokio natively, and manages internal Tokio
@@ -121,17 +322,85 @@
 //! ```ocaml
 //! external my_async_func_2 : (unit -> unit Lwt.t) -> unit Lwt.t = "my_async_func_2"
 //! ```
+//!
+//! ## Building without Tokio runtime
+//!
+//! Tokio runtime is enabled by `tokio` cargo feature, which is on by default.
+//! Stubs, which only await OCaml promises and do not do any Rust I/O, can
+//! disable default features, in which case no extra threads are started, and
+//! domain executor runs on OCaml domain thread only. [`domain_executor::tokio_rt`]
+//! and `tokio`/`blocking` arguments of `#[ocaml_lwt_interop::func]` are not
+//! available then, and [`signal`] is not supported.
+//!
+//! Timers are provided by [`time`] module, which is built on top of
+//! `Lwt_unix.sleep`, and works with or without the feature.
+//!
+//! ## Single-threaded mode
+//!
+//! By default, Rust I/O is performed on worker threads of the global Tokio
+//! runtime, and tasks on OCaml domain executor are woken up from there. For
+//! latency-sensitive deployments, `Rust_async.Runtime.init ~single_threaded:true ()`
+//! pairs domain executor with its own `current_thread` Tokio runtime instead
+//! (see [`domain_executor::DomainExecutor::new_single_threaded`]). Its I/O
+//! driver is registered in `Lwt_engine`, and is turned along with the executor
+//! on each tick, so that Rust sockets and OCaml sockets share one thread.
+//! Tokio timers are checked on a fixed `timer_interval` (10ms by default), as
+//! Tokio does not expose its next deadline, so timers may fire up to one period
+//! late, and the process wakes up with this period even when idle. The mode is only supported on Linux, `init` raises
+//! otherwise.
+//! `tokio::spawn` from tasks on domain executor spawns onto this runtime as
+//! well, while [`domain_executor::tokio_rt`] still refers to the global
+//! multi-threaded runtime.
+//!
+//! ## Iteration hooks mode
+//!
+//! By default, each wake-up of a task on domain executor sends a notification
+//! to Lwt event loop, which costs a pipe write and read. With
+//! `Rust_async.Runtime.init ~iteration_hooks:true ()`, the executor is run
+//! from `Lwt_main.Enter_iter_hooks` and `Leave_iter_hooks` instead, and the
+//! notification is only sent while the event loop might be blocked waiting for
+//! I/O (see [`domain_executor::DomainExecutor::set_iteration_hooks`]). This
+//! removes most of the notifications when Rust and Lwt tasks wake each other
+//! up in a ping-pong manner. `dune build @benchmark` compares both modes.
+//!
+//! # async-io integration
+//!
+//! Futures written against `async-io` or `smol` need the async-io reactor to
+//! be driven. `async-io` cargo feature starts a thread driving the reactor
+//! along with domain executor, so that such futures can be awaited directly
+//! in async stubs. The thread is registered with OCaml runtime, and runs its
+//! own executor, tasks can be spawned onto it via `domain_executor::async_io_rt`,
+//! which plays the same role as [`domain_executor::tokio_rt`] for Tokio.
+//!
+//! ```rust,ignore
+//! use std::time::Duration;
+//!
+//! #[ocaml_lwt_interop::func]
+//! pub fn my_async_io_sleep() -> () {
+//!     async_io::Timer::after(Duration::from_millis(10)).await;
+//! }
+//! ```
 
 pub mod async_func;
+pub mod blocking;
 mod caml_runtime;
+pub mod channel;
+pub mod concurrency;
 pub mod domain_executor;
 pub mod error;
+pub mod lwt_bytes;
+pub mod lwt_io;
 pub mod ml_box_future;
 pub mod notification;
 pub mod promise;
+pub mod root_table;
+pub mod signal;
 pub mod stubs;
+pub mod sync;
+pub mod tasks;
+pub mod time;
 
 #[macro_use]
 extern crate static_assertions;
 
-pub use ocaml_lwt_interop_macro::func;
+pub use ocaml_lwt_interop_macro::{blocking, func, methods, ocaml_trait};
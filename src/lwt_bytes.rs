@@ -0,0 +1,236 @@
+//! `LwtBytes` and `PinnedBuffer` are wrappers around `Lwt_bytes.t`, i.e. an
+//! off-heap OCaml Bigarray of bytes.
+//!
+//! Bigarray data is allocated outside of OCaml heap and is never moved by GC,
+//! so as long as the Bigarray itself is kept alive, Rust code can access its
+//! data without OCaml domain lock being held. `LwtBytes` keeps the Bigarray
+//! alive via an [`MlBox`] root.
+//!
+//! Passing `Bytes` or `string` into an async stub requires copying, as GC may
+//! move the value while the future is pending. `Lwt_bytes.t` can be passed
+//! without copying instead:
+//!
+//! ```rust
+//! use ocaml_lwt_interop::domain_executor::tokio_rt;
+//! use ocaml_lwt_interop::lwt_bytes::PinnedBuffer;
+//!
+//! #[ocaml_lwt_interop::func]
+//! pub fn my_checksum(buf: PinnedBuffer) -> i64 {
+//!     // Buffer is accessed on Tokio thread, without OCaml domain lock
+//!     tokio_rt()
+//!         .spawn(async move { buf.iter().map(|b| *b as i64).sum() })
+//!         .await
+//!         .unwrap()
+//! }
+//! ```
+//!
+//! Can be declared from OCaml side as follows:
+//!
+//! ```ocaml
+//! external my_checksum : Lwt_bytes.t -> int64 Lwt.t = "my_checksum"
+//! ```
+
+use ocaml::{FromValue, ToValue};
+use ocaml_gen::OCamlDesc;
+use ocaml_rs_smartptr::ml_box::MlBox;
+
+use crate::async_func::named_type_unique_id;
+
+// OCaml callbacks are registered in ../lib/Rust_async.ml
+ocaml::import! {
+    // `olwti_lwt_bytes_create` calls `Lwt_bytes.create`
+    fn olwti_lwt_bytes_create(len: isize) -> ocaml::Value;
+    // `olwti_lwt_bytes_proxy` calls `Lwt_bytes.proxy`
+    fn olwti_lwt_bytes_proxy(buf: ocaml::Value, off: isize, len: isize) -> ocaml::Value;
+}
+
+/// `LwtBytes` is a wrapper around ocaml::Value which is `Lwt_bytes.t`
+#[derive(Clone, Debug)]
+pub struct LwtBytes {
+    inner: MlBox,
+    ptr: *mut u8,
+    len: usize,
+}
+
+// As LwtBytes is a wraper on top of MlBox and a pointer to Bigarray data,
+// which is not moved by OCaml GC, we mark LwtBytes as Send + Sync as MlBox
+// itself
+unsafe impl Send for LwtBytes {}
+unsafe impl Sync for LwtBytes {}
+
+impl LwtBytes {
+    /// Allocates new `Lwt_bytes.t` of `len` bytes, calls `Lwt_bytes.create`
+    /// under the hood. Contents of the buffer are not initialized.
+    pub fn create(gc: &ocaml::Runtime, len: usize) -> Self {
+        let v = unsafe { olwti_lwt_bytes_create(gc, len as isize) }
+            .expect("olwti_lwt_bytes_create has thrown an exception");
+        Self::new(gc, v)
+    }
+
+    /// Allocates new `Lwt_bytes.t` and copies `data` into it.
+    pub fn from_slice(gc: &ocaml::Runtime, data: &[u8]) -> Self {
+        let bytes = Self::create(gc, data.len());
+        unsafe { bytes.as_mut_slice() }.copy_from_slice(data);
+        bytes
+    }
+
+    /// Returns a sub-buffer of `len` bytes starting at `off`, which shares the
+    /// data with this buffer, calls `Lwt_bytes.proxy` under the hood.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn sub(&self, gc: &ocaml::Runtime, off: usize, len: usize) -> Self {
+        assert!(
+            off.checked_add(len).is_some_and(|end| end <= self.len),
+            "LwtBytes::sub: range is out of bounds"
+        );
+        let v = unsafe {
+            olwti_lwt_bytes_proxy(gc, self.inner.as_value(gc), off as isize, len as isize)
+        }
+        .expect("olwti_lwt_bytes_proxy has thrown an exception");
+        Self::new(gc, v)
+    }
+
+    fn new(gc: &ocaml::Runtime, v: ocaml::Value) -> Self {
+        let mut array: ocaml::bigarray::Array1<u8> = ocaml::FromValue::from_value(v);
+        let data = array.data_mut();
+        let (ptr, len) = (data.as_mut_ptr(), data.len());
+        Self {
+            inner: MlBox::new(gc, v),
+            ptr,
+            len,
+        }
+    }
+
+    /// Returns the length of the buffer in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the contents of the buffer as a slice.
+    ///
+    /// # Safety
+    ///
+    /// OCaml code might be mutating the buffer concurrently, caller must make
+    /// sure that it's not the case while the slice is alive.
+    pub unsafe fn as_slice(&self) -> &[u8] {
+        std::slice::from_raw_parts(self.ptr, self.len)
+    }
+
+    /// Returns the contents of the buffer as a mutable slice.
+    ///
+    /// # Safety
+    ///
+    /// OCaml code might be accessing the buffer concurrently, caller must make
+    /// sure that it's not the case while the slice is alive. Caller must also
+    /// ensure that there are no other slices of the same buffer alive.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn as_mut_slice(&self) -> &mut [u8] {
+        std::slice::from_raw_parts_mut(self.ptr, self.len)
+    }
+}
+
+unsafe impl ocaml::ToValue for LwtBytes {
+    fn to_value(&self, gc: &ocaml::Runtime) -> ocaml::Value {
+        self.inner.as_value(gc)
+    }
+}
+
+unsafe impl ocaml::FromValue for LwtBytes {
+    fn from_value(v: ocaml::Value) -> Self {
+        /* see the comment in `Promise::from_value` */
+        let gc = unsafe { ocaml::Runtime::recover_handle() };
+        Self::new(gc, v)
+    }
+}
+
+impl OCamlDesc for LwtBytes {
+    fn ocaml_desc(_env: &::ocaml_gen::Env, _generics: &[&str]) -> String {
+        "Lwt_bytes.t".to_string()
+    }
+
+    fn unique_id() -> u128 {
+        named_type_unique_id("Lwt_bytes.t")
+    }
+}
+
+/// `PinnedBuffer` is an `Lwt_bytes.t`, which is handed over to Rust for
+/// exclusive use.
+///
+/// Unlike [`LwtBytes`], `PinnedBuffer` provides safe access to the data via
+/// [`Deref`](std::ops::Deref) and [`DerefMut`](std::ops::DerefMut) to `[u8]`.
+/// When accepting `PinnedBuffer` as an argument of a stub, the contract with
+/// OCaml side is that OCaml does not access the buffer until the promise,
+/// returned by the stub, is resolved. The data can be accessed from any
+/// thread, i.e. Tokio tasks can read and write it without OCaml domain lock.
+///
+/// `PinnedBuffer` can also be returned from stubs, handing the buffer back to
+/// OCaml.
+#[derive(Debug)]
+pub struct PinnedBuffer(LwtBytes);
+
+impl PinnedBuffer {
+    /// Allocates new `Lwt_bytes.t` of `len` bytes, filled with zeroes.
+    pub fn create(gc: &ocaml::Runtime, len: usize) -> Self {
+        let bytes = LwtBytes::create(gc, len);
+        unsafe { bytes.as_mut_slice() }.fill(0);
+        Self(bytes)
+    }
+
+    /// Wraps `LwtBytes` for exclusive use.
+    ///
+    /// # Safety
+    ///
+    /// Caller must make sure that neither OCaml code nor other `LwtBytes`
+    /// sharing the same data are accessing it while `PinnedBuffer` is alive.
+    pub unsafe fn new_unchecked(bytes: LwtBytes) -> Self {
+        Self(bytes)
+    }
+
+    /// Returns underlying `LwtBytes`.
+    pub fn into_inner(self) -> LwtBytes {
+        self.0
+    }
+}
+
+impl std::ops::Deref for PinnedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { self.0.as_slice() }
+    }
+}
+
+impl std::ops::DerefMut for PinnedBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { self.0.as_mut_slice() }
+    }
+}
+
+unsafe impl ocaml::ToValue for PinnedBuffer {
+    fn to_value(&self, gc: &ocaml::Runtime) -> ocaml::Value {
+        self.0.to_value(gc)
+    }
+}
+
+unsafe impl ocaml::FromValue for PinnedBuffer {
+    fn from_value(v: ocaml::Value) -> Self {
+        Self(LwtBytes::from_value(v))
+    }
+}
+
+impl OCamlDesc for PinnedBuffer {
+    fn ocaml_desc(_env: &::ocaml_gen::Env, _generics: &[&str]) -> String {
+        "Lwt_bytes.t".to_string()
+    }
+
+    fn unique_id() -> u128 {
+        named_type_unique_id("Lwt_bytes.t")
+    }
+}
@@ -0,0 +1,406 @@
+//! This module bridges Tokio's `AsyncRead`/`AsyncWrite` with `Lwt_io` channels.
+//!
+//! # Overview
+//!
+//! Both directions are supported:
+//!
+//! - [`Stream`] wraps any Rust `AsyncRead + AsyncWrite` (a `TcpStream`, a TLS
+//!   stream, a decompressor etc.), and is exposed to OCaml as
+//!   `Rust_async.Io.stream`. OCaml side turns it into a pair of
+//!   `Lwt_io.input_channel` and `Lwt_io.output_channel` built with
+//!   `Lwt_io.make`.
+//! - [`LwtReader`] and [`LwtWriter`] wrap `Rust_async.Io.reader` and
+//!   `Rust_async.Io.writer`, which OCaml side creates out of `Lwt_io` channels,
+//!   and implement `AsyncRead` and `AsyncWrite` respectively.
+//!
+//! Data is copied through `Lwt_bytes.t` buffers (see
+//! [`crate::lwt_bytes::LwtBytes`]), which live outside of OCaml heap, so Rust
+//! I/O operations can fill or drain them while OCaml is waiting on a promise.
+//!
+//! [`LwtReader`] and [`LwtWriter`] call OCaml functions, so they must only be
+//! polled from tasks running on
+//! [OCaml domain executor](crate::domain_executor::DomainExecutor).
+
+use std::{
+    future::Future,
+    io,
+    ops::Range,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use ocaml::{FromValue, ToValue};
+use ocaml_gen::OCamlDesc;
+use ocaml_rs_smartptr::ptr::DynBox;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::async_func::{named_type_unique_id, OCamlAsyncFunc};
+use crate::domain_executor::ocaml_runtime;
+use crate::lwt_bytes::LwtBytes;
+use crate::promise::PromiseFuture;
+
+/// Returns the range of `len` bytes at offset `off` within `buf`, or an error
+/// if the range does not fit into the buffer.
+fn buf_range(buf: &LwtBytes, off: usize, len: usize) -> io::Result<Range<usize>> {
+    match off.checked_add(len) {
+        Some(end) if end <= buf.len() => Ok(off..end),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "range of {len} bytes at offset {off} is out of bounds of buffer of {} bytes",
+                buf.len()
+            ),
+        )),
+    }
+}
+
+type BoxedReader = Pin<Box<dyn AsyncRead + Send>>;
+type BoxedWriter = Pin<Box<dyn AsyncWrite + Send>>;
+
+/// Rust stream, shared with OCaml via [`DynBox`].
+///
+/// Stream is split into read and write halves, each guarded by an async mutex,
+/// so reads and writes issued from OCaml can proceed concurrently.
+pub struct IoStream {
+    reader: tokio::sync::Mutex<BoxedReader>,
+    writer: tokio::sync::Mutex<BoxedWriter>,
+}
+
+impl IoStream {
+    /// Creates a new `IoStream` out of Rust stream.
+    pub fn new<S>(stream: S) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (reader, writer) = tokio::io::split(stream);
+        Self {
+            reader: tokio::sync::Mutex::new(Box::pin(reader)),
+            writer: tokio::sync::Mutex::new(Box::pin(writer)),
+        }
+    }
+
+    /// Reads up to `len` bytes into `buf` at offset `off`, returns the number
+    /// of bytes read, `0` means end of stream. Fails with
+    /// [`io::ErrorKind::InvalidInput`] if the range does not fit into `buf`.
+    pub async fn read(
+        &self,
+        buf: &LwtBytes,
+        off: usize,
+        len: usize,
+    ) -> io::Result<usize> {
+        use tokio::io::AsyncReadExt;
+        let range = buf_range(buf, off, len)?;
+        let dst = unsafe { &mut buf.as_mut_slice()[range] };
+        self.reader.lock().await.read(dst).await
+    }
+
+    /// Writes up to `len` bytes from `buf` at offset `off`, returns the number
+    /// of bytes written. Written data is flushed. Fails with
+    /// [`io::ErrorKind::InvalidInput`] if the range does not fit into `buf`.
+    pub async fn write(
+        &self,
+        buf: &LwtBytes,
+        off: usize,
+        len: usize,
+    ) -> io::Result<usize> {
+        use tokio::io::AsyncWriteExt;
+        let range = buf_range(buf, off, len)?;
+        let src = unsafe { &buf.as_slice()[range] };
+        let mut writer = self.writer.lock().await;
+        let n = writer.write(src).await?;
+        writer.flush().await?;
+        Ok(n)
+    }
+
+    /// Shuts down the write half of the stream.
+    pub async fn shutdown(&self) -> io::Result<()> {
+        use tokio::io::AsyncWriteExt;
+        self.writer.lock().await.shutdown().await
+    }
+}
+
+/// A Rust stream, which is handed over to OCaml as `Rust_async.Io.stream`.
+#[derive(Clone)]
+pub struct Stream(pub DynBox<IoStream>);
+
+impl Stream {
+    /// Wraps Rust stream to be handed over to OCaml.
+    pub fn new<S>(stream: S) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        Self(DynBox::new_shared(IoStream::new(stream)))
+    }
+}
+
+unsafe impl ocaml::ToValue for Stream {
+    fn to_value(&self, gc: &ocaml::Runtime) -> ocaml::Value {
+        self.0.to_value(gc)
+    }
+}
+
+unsafe impl ocaml::FromValue for Stream {
+    fn from_value(v: ocaml::Value) -> Self {
+        Self(DynBox::from_value(v))
+    }
+}
+
+impl OCamlDesc for Stream {
+    fn ocaml_desc(_env: &::ocaml_gen::Env, _generics: &[&str]) -> String {
+        "Rust_async.Io.stream".to_string()
+    }
+
+    fn unique_id() -> u128 {
+        named_type_unique_id("Rust_async.Io.stream")
+    }
+}
+
+fn to_io_error(err: crate::error::Error) -> io::Error {
+    io::Error::other(err)
+}
+
+/// Implements `AsyncRead` on top of `Rust_async.Io.reader`, which is a
+/// function reading into `Lwt_bytes.t`, i.e. `Lwt_bytes.t -> int -> int -> int
+/// Lwt.t`.
+pub struct LwtReader {
+    read: OCamlAsyncFunc<(LwtBytes, isize, isize), isize>,
+    /// Buffer passed to OCaml, reused across reads, and replaced with a bigger
+    /// one only when the caller's buffer outgrows it.
+    buffer: Option<LwtBytes>,
+    /// Pending read into `buffer`, along with the number of requested bytes.
+    pending: Option<(PromiseFuture<isize>, usize)>,
+    /// Range of data in `buffer`, which did not fit into the caller's buffer.
+    leftover: Option<Range<usize>>,
+}
+
+impl LwtReader {
+    /// Creates a new `LwtReader` out of `Rust_async.Io.reader`.
+    pub fn new(read: OCamlAsyncFunc<(LwtBytes, isize, isize), isize>) -> Self {
+        Self {
+            read,
+            buffer: None,
+            pending: None,
+            leftover: None,
+        }
+    }
+}
+
+unsafe impl ocaml::FromValue for LwtReader {
+    fn from_value(v: ocaml::Value) -> Self {
+        Self::new(OCamlAsyncFunc::from_value(v))
+    }
+}
+
+impl OCamlDesc for LwtReader {
+    fn ocaml_desc(_env: &::ocaml_gen::Env, _generics: &[&str]) -> String {
+        "Rust_async.Io.reader".to_string()
+    }
+
+    fn unique_id() -> u128 {
+        named_type_unique_id("Rust_async.Io.reader")
+    }
+}
+
+impl AsyncRead for LwtReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        loop {
+            if let Some(Range { start, end }) = this.leftover.take() {
+                let bytes = this.buffer.as_ref().unwrap();
+                let n = std::cmp::min(end - start, buf.remaining());
+                buf.put_slice(unsafe { &bytes.as_slice()[start..start + n] });
+                if start + n < end {
+                    this.leftover = Some(start + n..end);
+                }
+                return Poll::Ready(Ok(()));
+            }
+            if let Some((fut, len)) = &mut this.pending {
+                let len = *len;
+                let res = ready!(Pin::new(fut).poll(cx));
+                this.pending = None;
+                match res {
+                    // `0` from OCaml means end of file, which is signalled by
+                    // not filling anything into the buffer
+                    Ok(0) => return Poll::Ready(Ok(())),
+                    Ok(n) if n > 0 && n as usize <= len => {
+                        this.leftover = Some(0..n as usize);
+                        continue;
+                    }
+                    Ok(n) => {
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("reader returned {n} for a read of {len} bytes"),
+                        )))
+                    }
+                    Err(err) => return Poll::Ready(Err(to_io_error(err))),
+                }
+            }
+            let len = buf.remaining();
+            let bytes = match &this.buffer {
+                Some(bytes) if bytes.len() >= len => bytes.clone(),
+                _ => {
+                    let bytes = LwtBytes::create(&ocaml_runtime(), len);
+                    this.buffer = Some(bytes.clone());
+                    bytes
+                }
+            };
+            let fut = this.read.call((bytes, 0, len as isize));
+            this.pending = Some((fut, len));
+        }
+    }
+}
+
+/// Implements `AsyncWrite` on top of `Rust_async.Io.writer`, which is a record
+/// of OCaml functions for writing from `Lwt_bytes.t`, flushing and closing the
+/// underlying channel.
+pub struct LwtWriter {
+    write: OCamlAsyncFunc<(LwtBytes, isize, isize), isize>,
+    flush: OCamlAsyncFunc<(), ()>,
+    close: OCamlAsyncFunc<(), ()>,
+    /// Buffer passed to OCaml, reused across writes, and replaced with a bigger
+    /// one only when the caller's data outgrows it.
+    buffer: Option<LwtBytes>,
+    state: LwtWriterState,
+}
+
+/// Operation of [`LwtWriter`] which is currently in progress.
+enum LwtWriterState {
+    Idle,
+    Writing(PromiseFuture<isize>),
+    Flushing(PromiseFuture<()>),
+    Closing(PromiseFuture<()>),
+}
+
+impl LwtWriter {
+    /// Creates a new `LwtWriter` out of OCaml functions, which constitute
+    /// `Rust_async.Io.writer`.
+    pub fn new(
+        write: OCamlAsyncFunc<(LwtBytes, isize, isize), isize>,
+        flush: OCamlAsyncFunc<(), ()>,
+        close: OCamlAsyncFunc<(), ()>,
+    ) -> Self {
+        Self {
+            write,
+            flush,
+            close,
+            buffer: None,
+            state: LwtWriterState::Idle,
+        }
+    }
+
+    /// Drives the operation in progress, if any, to completion, discarding its
+    /// result unless it's an error. The caller may have abandoned it, e.g.
+    /// dropped a write future, so it's finished before an operation of another
+    /// kind starts.
+    fn poll_idle(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let res = match &mut self.state {
+            LwtWriterState::Idle => return Poll::Ready(Ok(())),
+            LwtWriterState::Writing(fut) => ready!(Pin::new(fut).poll(cx)).map(drop),
+            LwtWriterState::Flushing(fut) | LwtWriterState::Closing(fut) => {
+                ready!(Pin::new(fut).poll(cx))
+            }
+        };
+        self.state = LwtWriterState::Idle;
+        Poll::Ready(res.map_err(to_io_error))
+    }
+}
+
+unsafe impl ocaml::FromValue for LwtWriter {
+    fn from_value(v: ocaml::Value) -> Self {
+        unsafe {
+            Self::new(
+                OCamlAsyncFunc::from_value(v.field(0)),
+                OCamlAsyncFunc::from_value(v.field(1)),
+                OCamlAsyncFunc::from_value(v.field(2)),
+            )
+        }
+    }
+}
+
+impl OCamlDesc for LwtWriter {
+    fn ocaml_desc(_env: &::ocaml_gen::Env, _generics: &[&str]) -> String {
+        "Rust_async.Io.writer".to_string()
+    }
+
+    fn unique_id() -> u128 {
+        named_type_unique_id("Rust_async.Io.writer")
+    }
+}
+
+impl AsyncWrite for LwtWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            match &mut this.state {
+                LwtWriterState::Idle => {
+                    if buf.is_empty() {
+                        return Poll::Ready(Ok(0));
+                    }
+                    let bytes = match &this.buffer {
+                        Some(bytes) if bytes.len() >= buf.len() => bytes.clone(),
+                        _ => {
+                            let bytes = LwtBytes::create(&ocaml_runtime(), buf.len());
+                            this.buffer = Some(bytes.clone());
+                            bytes
+                        }
+                    };
+                    let dst = unsafe { &mut bytes.as_mut_slice()[..buf.len()] };
+                    dst.copy_from_slice(buf);
+                    let fut = this.write.call((bytes, 0, buf.len() as isize));
+                    this.state = LwtWriterState::Writing(fut);
+                }
+                LwtWriterState::Writing(fut) => {
+                    let res = ready!(Pin::new(fut).poll(cx));
+                    this.state = LwtWriterState::Idle;
+                    return Poll::Ready(res.map(|n| n as usize).map_err(to_io_error));
+                }
+                _ => ready!(this.poll_idle(cx))?,
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            match &mut this.state {
+                LwtWriterState::Idle => {
+                    this.state = LwtWriterState::Flushing(this.flush.call(()));
+                }
+                LwtWriterState::Flushing(fut) => {
+                    let res = ready!(Pin::new(fut).poll(cx));
+                    this.state = LwtWriterState::Idle;
+                    return Poll::Ready(res.map_err(to_io_error));
+                }
+                _ => ready!(this.poll_idle(cx))?,
+            }
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            match &mut this.state {
+                LwtWriterState::Idle => {
+                    this.state = LwtWriterState::Closing(this.close.call(()));
+                }
+                LwtWriterState::Closing(fut) => {
+                    let res = ready!(Pin::new(fut).poll(cx));
+                    this.state = LwtWriterState::Idle;
+                    return Poll::Ready(res.map_err(to_io_error));
+                }
+                _ => ready!(this.poll_idle(cx))?,
+            }
+        }
+    }
+}
@@ -17,72 +17,66 @@
 //!
 //! # Implementation Details
 //!
-//! The `MlBoxFuture` is largely based on the timer future example from the Rust
-//! `async` book:
-//! [Wakeups](https://rust-lang.github.io/async-book/02_execution/03_wakeups.html).
+//! `MlBoxFuture` is shared between OCaml (via `DynBox`) and the awaiting Rust
+//! future, so it is resolved and polled through a shared reference. Instead of
+//! a `Mutex`, it uses an atomic state flag guarding the value slot, and an
+//! [`AtomicWaker`] to store the waker. Resolving the future writes the value,
+//! publishes it by updating the state and wakes up the waker. Polling the
+//! future registers the waker first, and then checks the state, so that a
+//! wake-up can't be missed.
 //!
-//! It uses an `Arc<Mutex<SharedState>>` to manage shared state between the
-//! future and the code that will resolve or reject it.
-//! When the future is polled, it checks if the value is available. If not, it
-//! stores the waker to be woken up later.
-//! When the OCaml side resolves or rejects the future, it sets the value and
-//! wakes up the waker, allowing the future to complete.
+//! No allocations happen after `MlBoxFuture` is created, except for waker
+//! cloning, which is up to the executor. Despite the name, the value is kept
+//! alive by a [`Root`] of the root table rather than by an `MlBox`, see
+//! [`crate::root_table`].
 
 use std::{
+    cell::UnsafeCell,
     future::Future,
     pin::Pin,
-    sync::{Arc, Mutex},
-    task::{Context, Poll, Waker},
+    sync::atomic::{AtomicU8, Ordering},
+    task::{Context, Poll},
 };
 
-use ocaml_rs_smartptr::ml_box::MlBox;
+use crate::root_table::Root;
+use atomic_waker::AtomicWaker;
 
-/// Shared state between the `MlBoxFuture` and the code that resolves or rejects
-/// it.
-///
-/// This struct holds the result value (once it's available), the waker to wake
-/// up the future when it's ready, and a flag indicating whether the future has
-/// been completed.
-#[derive(Debug)]
-struct MlBoxFutureSharedState {
-    /// The result value of the future, set when the future is resolved or
-    /// rejected.
-    value: Option<Result<MlBox, crate::error::Error>>,
-    /// The waker to notify the executor when the future is ready.
-    waker: Option<Waker>,
-    /// Indicates whether the future has been completed.
-    completed: bool,
-}
+/// The future is neither resolved nor rejected yet.
+const EMPTY: u8 = 0;
+/// The value is being written by `set_value`.
+const WRITING: u8 = 1;
+/// The value is available.
+const READY: u8 = 2;
+/// The value has been taken by `poll_value`.
+const TAKEN: u8 = 3;
 
 /// A future that represents a computation which will eventually produce an
-/// `MlBox`.
+/// OCaml value, kept alive by a [`Root`].
 ///
 /// `MlBoxFuture` is used to bridge OCaml's Lwt futures with Rust's async code.
 /// It can be awaited in Rust, and is resolved or rejected from the OCaml side.
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub struct MlBoxFuture {
-    /// Shared state between the future and the resolver.
-    shared_state: Arc<Mutex<MlBoxFutureSharedState>>,
+    /// One of `EMPTY`, `WRITING`, `READY` or `TAKEN`, guards access to `value`.
+    state: AtomicU8,
+    /// The result value of the future, set when the future is resolved or
+    /// rejected.
+    value: UnsafeCell<Option<Result<Root, crate::error::Error>>>,
+    /// The waker to notify the executor when the future is ready.
+    waker: AtomicWaker,
 }
 
+// `value` is only accessed by the side, which has successfully transitioned
+// `state` (to `WRITING` or to `TAKEN`), the rest is atomic
+unsafe impl Send for MlBoxFuture {}
+unsafe impl Sync for MlBoxFuture {}
+
 impl Future for MlBoxFuture {
-    type Output = Result<MlBox, crate::error::Error>;
+    type Output = Result<Root, crate::error::Error>;
 
-    /// Polls the future to check if it has been resolved or rejected.
-    ///
-    /// If the value is available, returns `Poll::Ready` with the result.
-    /// Otherwise, stores the waker and returns `Poll::Pending`.
+    /// See [`MlBoxFuture::poll_value`].
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let mut shared_state = self.shared_state.lock().unwrap();
-        match shared_state.value.take() {
-            Some(maybe_value) => Poll::Ready(maybe_value),
-            None => {
-                // Store the waker so we can wake this task when the value
-                // is set.
-                shared_state.waker = Some(cx.waker().clone());
-                Poll::Pending
-            }
-        }
+        self.get_mut().poll_value(cx)
     }
 }
 
@@ -92,12 +86,44 @@ impl MlBoxFuture {
     /// This future can later be resolved or rejected using the `resolve` or
     /// `reject` methods.
     pub fn new() -> Self {
-        let shared_state = Arc::new(Mutex::new(MlBoxFutureSharedState {
-            value: None,
-            waker: None,
-            completed: false,
-        }));
-        MlBoxFuture { shared_state }
+        MlBoxFuture {
+            state: AtomicU8::new(EMPTY),
+            value: UnsafeCell::new(None),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    /// Polls the future through a shared reference, checking if it has been
+    /// resolved or rejected.
+    ///
+    /// If the value is available, returns `Poll::Ready` with the result.
+    /// Otherwise, stores the waker and returns `Poll::Pending`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value has already been taken.
+    pub fn poll_value(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Root, crate::error::Error>> {
+        if self.state.load(Ordering::Acquire) != READY {
+            // Register the waker before checking the state again, so that the
+            // value set in between is not missed
+            self.waker.register(cx.waker());
+        }
+        match self.state.compare_exchange(
+            READY,
+            TAKEN,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => {
+                let value = unsafe { (*self.value.get()).take() };
+                Poll::Ready(value.expect("MlBoxFuture value is missing"))
+            }
+            Err(TAKEN) => panic!("MlBoxFuture polled after completion"),
+            Err(_) => Poll::Pending,
+        }
     }
 
     /// Sets the value of the future and wakes up the waker if it's stored.
@@ -107,23 +133,24 @@ impl MlBoxFuture {
     /// # Panics
     ///
     /// Panics if the future has already been completed.
-    fn set_value(&self, value: Result<MlBox, crate::error::Error>) {
-        let mut shared_state = self.shared_state.lock().unwrap();
-        if shared_state.completed {
+    fn set_value(&self, value: Result<Root, crate::error::Error>) {
+        if self
+            .state
+            .compare_exchange(EMPTY, WRITING, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
             panic!("Attempt to resolve an already resolved promise")
         }
-        shared_state.completed = true;
-        shared_state.value = Some(value);
+        unsafe { *self.value.get() = Some(value) };
+        self.state.store(READY, Ordering::Release);
         // Wake up the waker to notify that the future is ready.
-        if let Some(waker) = shared_state.waker.take() {
-            waker.wake()
-        }
+        self.waker.wake();
     }
 
-    /// Resolves the future with the given `MlBox` value.
+    /// Resolves the future with the given rooted value.
     ///
     /// Typically called from the OCaml side when the Lwt future is fulfilled.
-    pub fn resolve(&self, value: MlBox) {
+    pub fn resolve(&self, value: Root) {
         self.set_value(Ok(value))
     }
 
@@ -4,12 +4,15 @@
 //! bridging OCaml's Lwt promises with Rust's `Future` trait.
 //!                                                                                                                                                                                           
 
-use crate::{domain_executor::ocaml_runtime, ml_box_future::MlBoxFuture};
+use crate::{
+    domain_executor::ocaml_runtime, ml_box_future::MlBoxFuture, root_table::Root,
+};
 use highway::{HighwayHash, HighwayHasher};
+use ocaml::ToValue;
 use ocaml_gen::{const_random, OCamlDesc};
-use ocaml_rs_smartptr::ml_box::MlBox;
 use ocaml_rs_smartptr::ptr::DynBox;
 use std::{
+    cell::RefCell,
     future::{Future, IntoFuture},
     hash::Hash,
     marker::PhantomData,
@@ -22,61 +25,222 @@ use std::{
 ocaml::import! {
     // `olwti_lwt_task` calls `Lwt.task`, and returns promise and resolver
     fn olwti_lwt_task() -> (ocaml::Value, ocaml::Value);
-    // `olwti_lwt_wakeup_later` calls `Lwt.wakeup_later`
-    fn olwti_lwt_wakeup_later(resolver: ocaml::Value, value: ocaml::Value) -> Result<(), String>;
-    // `olwti_lwt_wakeup_later_exn` calls `Lwt.wakeup_later_exn`
-    fn olwti_lwt_wakeup_later_exn(resolver: ocaml::Value, msg: String) -> Result<(), String>;
+    // `olwti_lwt_wakeup_batch` walks the array of `resolver, kind, payload`
+    // triples in order, where `resolver` and `payload` are slots of the root
+    // table, and calls `Lwt.wakeup_later` or `Lwt.wakeup_later_exn` depending
+    // on `kind` (one of `SETTLE_*` constants). Exceptions raised by them are
+    // passed to `Lwt.async_exception_hook`
+    fn olwti_lwt_wakeup_batch(batch: Vec<isize>);
+    // `olwti_lwt_return` calls `Lwt.return`
+    fn olwti_lwt_return(value: ocaml::Value) -> ocaml::Value;
+    // `olwti_lwt_fail_with` calls `Lwt.fail_with`
+    fn olwti_lwt_fail_with(msg: String) -> ocaml::Value;
+    // `olwti_lwt_fail` calls `Lwt.fail` with the given exception
+    fn olwti_lwt_fail(exn: ocaml::Value) -> ocaml::Value;
+    // `olwti_lwt_join`, `olwti_lwt_pick`, `olwti_lwt_choose` and `olwti_lwt_all`
+    // call corresponding Lwt combinators on an array of promises
+    fn olwti_lwt_join(promises: ocaml::Value) -> ocaml::Value;
+    fn olwti_lwt_pick(promises: ocaml::Value) -> ocaml::Value;
+    fn olwti_lwt_choose(promises: ocaml::Value) -> ocaml::Value;
+    fn olwti_lwt_all(promises: ocaml::Value) -> ocaml::Value;
+    // `olwti_lwt_both` calls `Lwt.both`
+    fn olwti_lwt_both(a: ocaml::Value, b: ocaml::Value) -> ocaml::Value;
+    // `olwti_lwt_peek` checks `Lwt.state`, returns `None` if the promise is
+    // pending, and the value or the exception message otherwise
+    fn olwti_lwt_peek(fut: ocaml::Value) -> Option<Result<ocaml::Value, String>>;
     // `olwti_wrap_lwt_future` creates new `MlBoxFuture`, and links
     // resolution/rejection of `fut` (which is `'a Lwt.t``) to corresponding
     // `MlBoxFuture`
     fn olwti_wrap_lwt_future(fut: ocaml::Value) -> DynBox<MlBoxFuture>;
 }
 
+/// `payload` is the value to resolve with
+const SETTLE_RESOLVE: isize = 0;
+/// `payload` is the message of `Failure` to reject with
+const SETTLE_REJECT: isize = 1;
+/// `payload` is the exception to reject with
+const SETTLE_REJECT_EXN: isize = 2;
+
+/// Settlement of `'a Lwt.u`, queued until the end of executor tick. Both
+/// the resolver and the payload are rooted, as settlements are passed to OCaml
+/// by slot indices.
+struct Settlement {
+    resolver: Root,
+    /// One of `SETTLE_*` constants
+    kind: isize,
+    payload: Root,
+}
+
+thread_local! {
+    /// Settlements queued by tasks during current executor tick, `None` when
+    /// the executor is not ticking
+    static SETTLEMENTS: RefCell<Option<Vec<Settlement>>> = const { RefCell::new(None) };
+}
+
+/// Applies `settlements` in order in one OCaml call.
+fn wakeup(gc: &ocaml::Runtime, settlements: &[Settlement]) {
+    let batch: Vec<isize> = settlements
+        .iter()
+        .flat_map(|settlement| {
+            [
+                settlement.resolver.index() as isize,
+                settlement.kind,
+                settlement.payload.index() as isize,
+            ]
+        })
+        .collect();
+    unsafe { olwti_lwt_wakeup_batch(gc, batch) }
+        .expect("olwti_lwt_wakeup_batch has thrown an exception");
+}
+
+/// Queues settlements of promises for the duration of executor tick, see
+/// [`crate::domain_executor::DomainExecutor::tick`].
+///
+/// When thousands of tasks complete within one tick, calling into OCaml for
+/// each of them dominates. Instead, while `SettlementBatch` is alive,
+/// [`Resolver`] queues settlements, and dropping `SettlementBatch` passes them
+/// all to OCaml in one call, in the order they were made.
+///
+/// Unlike `Lwt.wakeup_later`, which changes the state of the promise right
+/// away, a queued settlement only takes effect at the end of the tick. Until
+/// then the promise stays pending (`Lwt.state` is `Sleep`) for the rest of
+/// the tick, both for OCaml code called synchronously by tasks and for other
+/// tasks awaiting it, including the fast path of [`PromiseFuture`], so such
+/// tasks only observe the settlement on the next tick. All settlements of the
+/// tick are applied before the control returns to Lwt event loop.
+///
+/// Settling a promise, which is already settled, raises `Invalid_argument` in
+/// OCaml. Such failures do not stop the rest of the batch, and are reported
+/// to `Lwt.async_exception_hook`, like other exceptions nobody can handle.
+pub(crate) struct SettlementBatch {
+    _private: (),
+}
+
+impl SettlementBatch {
+    /// Starts queueing settlements on current thread, returns `None` if they
+    /// are already queued by an outer `SettlementBatch`.
+    pub(crate) fn begin() -> Option<Self> {
+        SETTLEMENTS.with(|queue| {
+            let mut queue = queue.borrow_mut();
+            if queue.is_some() {
+                return None;
+            }
+            *queue = Some(Vec::new());
+            Some(Self { _private: () })
+        })
+    }
+}
+
+impl Drop for SettlementBatch {
+    fn drop(&mut self) {
+        let queue = SETTLEMENTS.with(|queue| queue.borrow_mut().take());
+        let queue = match queue {
+            Some(queue) if !queue.is_empty() => queue,
+            _ => return,
+        };
+        if std::thread::panicking() {
+            // Settlements are dropped, calling into OCaml while unwinding is
+            // not an option
+            return;
+        }
+        wakeup(&ocaml_runtime(), &queue);
+    }
+}
+
 /// `Resolver<T>` is a wrapper around ocaml::Value which is `'a Lwt.u``,
 /// where `'a == T`
+///
+/// Settlements made by tasks during executor tick are queued, and passed to
+/// OCaml in one call at the end of the tick, in the order they were made.
 pub struct Resolver<T>
 where
     T: ocaml::ToValue,
 {
-    resolver: MlBox,
+    resolver: Root,
     _marker: AssertUnwindSafe<PhantomData<T>>,
 }
 
-// As Resolver is a wraper on top of MlBox, we mark Resolver as Send + Sync as
-// MlBox itself
+// As Resolver is a wraper on top of Root, we mark Resolver as Send + Sync as
+// Root itself
 unsafe impl<T: ocaml::ToValue> Send for Resolver<T> {}
 unsafe impl<T: ocaml::ToValue> Sync for Resolver<T> {}
 
 assert_impl_all!(Resolver<ocaml::Value>: Send, Sync, UnwindSafe, RefUnwindSafe);
 
 impl<T: ocaml::ToValue> Resolver<T> {
+    /// Queues the settlement if [`SettlementBatch`] is active on current
+    /// thread, applies it right away otherwise. `payload` is rooted right
+    /// away, so that it's not moved by OCaml GC meanwhile.
+    fn settle_with(self, gc: &ocaml::Runtime, kind: isize, payload: ocaml::Value) {
+        let settlement = Settlement {
+            resolver: self.resolver,
+            kind,
+            payload: Root::new(gc, payload),
+        };
+        let settlement = SETTLEMENTS.with(|queue| match queue.borrow_mut().as_mut() {
+            Some(queue) => {
+                queue.push(settlement);
+                None
+            }
+            None => Some(settlement),
+        });
+        if let Some(settlement) = settlement {
+            wakeup(gc, &[settlement]);
+        }
+    }
+
     /// Resolves the `'a Lwt.u` via `Lwt.wakeup_later`
     pub fn resolve(self, gc: &ocaml::Runtime, v: &T) {
-        let resolver = self.resolver.as_value(gc);
-        unsafe { olwti_lwt_wakeup_later(gc, resolver, v.to_value(gc)) }
-            .expect("olwti_lwt_wakeup_later has thrown an exception")
-            .unwrap()
+        self.settle_with(gc, SETTLE_RESOLVE, v.to_value(gc));
     }
 
     /// Rejects the `'a Lwt.u` via `Lwt.wakeup_later_exn`
     pub fn reject(self, gc: &ocaml::Runtime, msg: String) {
-        let resolver = self.resolver.as_value(gc);
-        unsafe { olwti_lwt_wakeup_later_exn(gc, resolver, msg) }
-            .expect("olwti_lwt_wakeup_later_exn has thrown an exception")
-            .unwrap()
+        self.settle_with(gc, SETTLE_REJECT, msg.to_value(gc));
+    }
+
+    /// Rejects the `'a Lwt.u` via `Lwt.wakeup_later_exn` with `exn`, which
+    /// must convert to OCaml exception value
+    pub fn reject_exn<E: ocaml::ToValue>(self, gc: &ocaml::Runtime, exn: &E) {
+        self.settle_with(gc, SETTLE_REJECT_EXN, exn.to_value(gc));
+    }
+
+    /// Resolves the `'a Lwt.u` with `Ok` value, or rejects it with `Failure`
+    /// carrying the message of `Err` value
+    pub fn settle<E: std::fmt::Display>(self, gc: &ocaml::Runtime, res: &Result<T, E>) {
+        match res {
+            Ok(v) => self.resolve(gc, v),
+            Err(err) => self.reject(gc, err.to_string()),
+        }
+    }
+
+    /// Resolves the `'a Lwt.u` with `Ok` value, or rejects it with `Err` value,
+    /// which must convert to OCaml exception value
+    pub fn settle_exn<E: ocaml::ToValue>(self, gc: &ocaml::Runtime, res: &Result<T, E>) {
+        match res {
+            Ok(v) => self.resolve(gc, v),
+            Err(exn) => self.reject_exn(gc, exn),
+        }
     }
 }
 
+/// `LwtResult<T, E>` is a `Promise` of a result, i.e. `('a, 'e) Lwt_result.t`,
+/// where `'a == T` and `'e == E`.
+///
+/// [`Promise::into_result_future`] converts it into a future, resolving to
+/// `Result<T, E>`.
+pub type LwtResult<T, E> = Promise<Result<T, E>>;
+
 /// `Promise<T>` is a wrapper around ocaml::Value which is `'a Lwt.t``,
 /// where `'a == T`
 #[derive(Debug)]
 pub struct Promise<T> {
-    inner: MlBox,
+    inner: Root,
     _marker: AssertUnwindSafe<PhantomData<T>>,
 }
 
-// As Promise is a wraper on top of MlBox, we mark Promise as Send + Sync as
-// MlBox itself
+// As Promise is a wraper on top of Root, we mark Promise as Send + Sync as
+// Root itself
 unsafe impl<T> Send for Promise<T> {}
 unsafe impl<T> Sync for Promise<T> {}
 
@@ -90,16 +254,130 @@ where
     pub fn new(gc: &ocaml::Runtime) -> (Promise<T>, Resolver<T>) {
         let (v_fut, v_resolver) = unsafe { olwti_lwt_task(gc) }
             .expect("olwti_lwt_task has thrown an exception");
+        let [fut_root, resolver_root] = Root::new_many(gc, [v_fut, v_resolver]);
         let fut: Promise<T> = Promise {
-            inner: MlBox::new(gc, v_fut),
+            inner: fut_root,
             _marker: AssertUnwindSafe(PhantomData),
         };
         let resolver: Resolver<T> = Resolver {
-            resolver: MlBox::new(gc, v_resolver),
+            resolver: resolver_root,
             _marker: AssertUnwindSafe(PhantomData),
         };
         (fut, resolver)
     }
+
+    /// Creates an already resolved promise via `Lwt.return`
+    pub fn resolved(gc: &ocaml::Runtime, v: &T) -> Promise<T> {
+        let v_fut = unsafe { olwti_lwt_return(gc, v.to_value(gc)) }
+            .expect("olwti_lwt_return has thrown an exception");
+        Self::from_raw(gc, v_fut)
+    }
+
+    /// Creates an already rejected promise via `Lwt.fail_with`
+    pub fn rejected(gc: &ocaml::Runtime, msg: String) -> Promise<T> {
+        let v_fut = unsafe { olwti_lwt_fail_with(gc, msg) }
+            .expect("olwti_lwt_fail_with has thrown an exception");
+        Self::from_raw(gc, v_fut)
+    }
+
+    /// Creates an already rejected promise via `Lwt.fail` with `exn`, which
+    /// must convert to OCaml exception value
+    pub fn rejected_exn<E: ocaml::ToValue>(gc: &ocaml::Runtime, exn: &E) -> Promise<T> {
+        let v_fut = unsafe { olwti_lwt_fail(gc, exn.to_value(gc)) }
+            .expect("olwti_lwt_fail has thrown an exception");
+        Self::from_raw(gc, v_fut)
+    }
+
+    /// Creates an already settled promise, see [`Resolver::settle`]
+    pub fn settled<E: std::fmt::Display>(
+        gc: &ocaml::Runtime,
+        res: &Result<T, E>,
+    ) -> Promise<T> {
+        match res {
+            Ok(v) => Self::resolved(gc, v),
+            Err(err) => Self::rejected(gc, err.to_string()),
+        }
+    }
+
+    /// Creates an already settled promise, see [`Resolver::settle_exn`]
+    pub fn settled_exn<E: ocaml::ToValue>(
+        gc: &ocaml::Runtime,
+        res: &Result<T, E>,
+    ) -> Promise<T> {
+        match res {
+            Ok(v) => Self::resolved(gc, v),
+            Err(exn) => Self::rejected_exn(gc, exn),
+        }
+    }
+
+    pub(crate) fn from_raw(gc: &ocaml::Runtime, v_fut: ocaml::Value) -> Promise<T> {
+        Promise {
+            inner: Root::new(gc, v_fut),
+            _marker: AssertUnwindSafe(PhantomData),
+        }
+    }
+}
+
+/// Combines `promises` via `Lwt.join`, i.e. the promise is resolved once all
+/// `promises` are resolved, or rejected once all of them are settled, and some
+/// were rejected.
+pub fn join(gc: &ocaml::Runtime, promises: Vec<Promise<()>>) -> Promise<()> {
+    let v_fut = unsafe { olwti_lwt_join(gc, promises.to_value(gc)) }
+        .expect("olwti_lwt_join has thrown an exception");
+    Promise::from_raw(gc, v_fut)
+}
+
+/// Combines `promises` via `Lwt.pick`, i.e. the promise is settled as the
+/// first of `promises` to settle, and the rest of them are cancelled.
+///
+/// Cancellation only affects the OCaml side: cancelled promises are rejected
+/// with `Lwt.Canceled`, but Rust tasks, which settle them, such as tasks of
+/// `#[ocaml_lwt_interop::func]` stubs, are not cancelled and keep running to
+/// completion, settling their [`Resolver`]s has no effect then. Rust work,
+/// which has to stop, needs its own signal, e.g. a [`crate::sync::Notify`].
+pub fn pick<T: ocaml::ToValue>(
+    gc: &ocaml::Runtime,
+    promises: Vec<Promise<T>>,
+) -> Promise<T> {
+    let v_fut = unsafe { olwti_lwt_pick(gc, promises.to_value(gc)) }
+        .expect("olwti_lwt_pick has thrown an exception");
+    Promise::from_raw(gc, v_fut)
+}
+
+/// Combines `promises` via `Lwt.choose`, i.e. the promise is settled as the
+/// first of `promises` to settle, the rest of them are left pending.
+pub fn choose<T: ocaml::ToValue>(
+    gc: &ocaml::Runtime,
+    promises: Vec<Promise<T>>,
+) -> Promise<T> {
+    let v_fut = unsafe { olwti_lwt_choose(gc, promises.to_value(gc)) }
+        .expect("olwti_lwt_choose has thrown an exception");
+    Promise::from_raw(gc, v_fut)
+}
+
+/// Combines two promises via `Lwt.both`, i.e. the promise is resolved with
+/// both values once both promises are resolved, or rejected once either of
+/// them is rejected.
+pub fn both<A, B>(gc: &ocaml::Runtime, a: Promise<A>, b: Promise<B>) -> Promise<(A, B)>
+where
+    A: ocaml::ToValue,
+    B: ocaml::ToValue,
+{
+    let v_fut = unsafe { olwti_lwt_both(gc, a.to_value(gc), b.to_value(gc)) }
+        .expect("olwti_lwt_both has thrown an exception");
+    Promise::from_raw(gc, v_fut)
+}
+
+/// Combines `promises` via `Lwt.all`, i.e. the promise is resolved with values
+/// of all `promises` in the same order once all of them are resolved, or
+/// rejected once any of them is rejected.
+pub fn all<T: ocaml::ToValue>(
+    gc: &ocaml::Runtime,
+    promises: Vec<Promise<T>>,
+) -> Promise<Vec<T>> {
+    let v_fut = unsafe { olwti_lwt_all(gc, promises.to_value(gc)) }
+        .expect("olwti_lwt_all has thrown an exception");
+    Promise::from_raw(gc, v_fut)
 }
 
 unsafe impl<T> ocaml::ToValue for Promise<T>
@@ -121,7 +399,7 @@ where
          * a weird thread... */
         let gc = unsafe { ocaml::Runtime::recover_handle() };
         Self {
-            inner: MlBox::new(gc, v),
+            inner: Root::new(gc, v),
             _marker: AssertUnwindSafe(PhantomData),
         }
     }
@@ -139,6 +417,18 @@ where
     }
 }
 
+impl<T, E> Promise<Result<T, E>>
+where
+    T: ocaml::FromValue + Send + 'static,
+    E: ocaml::FromValue + From<crate::error::Error> + Send + 'static,
+{
+    /// Converts `('a, 'e) Lwt_result.t` into a future, resolving to
+    /// `Result<T, E>`. Rejection of the promise is converted into `E`.
+    pub fn into_result_future(self) -> LwtResultFuture<T, E> {
+        self.into_future().into_result()
+    }
+}
+
 impl<T> OCamlDesc for Promise<T>
 where
     T: OCamlDesc,
@@ -173,26 +463,32 @@ where
 /// - **Separation of Concerns**: `Promise<T>` represents the promise itself,
 ///   while `PromiseFuture<T>` handles its execution.
 /// - **State Management**: It maintains internal states (`NotStarted`,
-///   `Running`, `Completed`) necessary for the `Future` trait.
+///   `Waiting`, `Completed`) necessary for the `Future` trait.
 ///
 /// Therefore, we avoid implementing `Future` directly on `Promise<T>` to keep
 /// concerns separated and the code maintainable.
+///
+/// `PromiseFuture` is a hand-written state machine, polling the
+/// [`MlBoxFuture`] it waits on directly, so that awaiting does not allocate
+/// anything besides the `MlBoxFuture` itself. Promises, which are already
+/// resolved or rejected when first polled, complete right away, without
+/// creating `MlBoxFuture` at all.
 pub struct PromiseFuture<T> {
-    /// Holds the OCaml promise; taken when the future starts.
-    promise: Option<MlBox>,
     /// Manages the internal state of the future.
-    state: PromiseFutureState<T>,
+    state: PromiseFutureState,
+    _marker: PhantomData<fn() -> T>,
 }
 
 // Ensures that `PromiseFuture` is `Send` and `Unpin`.
 assert_impl_all!(PromiseFuture<()>: Send, Unpin);
 
 /// Represents the different states during the future's lifecycle.
-enum PromiseFutureState<T> {
-    /// Initial state before starting.
-    NotStarted,
-    /// Running state, holds the pinned future.
-    Running(Pin<Box<dyn Future<Output = Result<T, crate::error::Error>> + Send>>),
+enum PromiseFutureState {
+    /// Initial state before starting, holds the OCaml promise.
+    NotStarted(Root),
+    /// Waiting state, holds the `MlBoxFuture`, which is resolved once the
+    /// OCaml promise is resolved.
+    Waiting(DynBox<MlBoxFuture>),
     /// State after completion.
     Completed,
 }
@@ -204,8 +500,45 @@ where
     /// Creates a new `PromiseFuture` from a `Promise<T>`.
     fn new(promise: Promise<T>) -> Self {
         Self {
-            promise: Some(promise.inner.clone()),
-            state: PromiseFutureState::NotStarted,
+            state: PromiseFutureState::NotStarted(promise.inner),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, E> PromiseFuture<Result<T, E>>
+where
+    T: ocaml::FromValue + Send + 'static,
+    E: ocaml::FromValue + From<crate::error::Error> + Send + 'static,
+{
+    /// Converts the future of `('a, 'e) Lwt_result.t` into a future, resolving
+    /// to `Result<T, E>`. Rejection of the promise is converted into `E`.
+    pub fn into_result(self) -> LwtResultFuture<T, E> {
+        LwtResultFuture { inner: self }
+    }
+}
+
+/// `LwtResultFuture<T, E>` is a future of `('a, 'e) Lwt_result.t`, which
+/// flattens promise rejection into `E`.
+pub struct LwtResultFuture<T, E> {
+    inner: PromiseFuture<Result<T, E>>,
+}
+
+assert_impl_all!(LwtResultFuture<(), ()>: Send, Unpin);
+
+impl<T, E> Future for LwtResultFuture<T, E>
+where
+    T: ocaml::FromValue + Send + 'static,
+    E: ocaml::FromValue + From<crate::error::Error> + Send + 'static,
+{
+    type Output = Result<T, E>;
+
+    /// See [`PromiseFuture::poll`].
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match Pin::new(&mut self.get_mut().inner).poll(cx) {
+            Poll::Ready(Ok(res)) => Poll::Ready(res),
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err.into())),
+            Poll::Pending => Poll::Pending,
         }
     }
 }
@@ -225,46 +558,52 @@ where
         let this = self.get_mut();
 
         loop {
-            match &mut this.state {
+            match &this.state {
                 // Initialize the future if not started.
-                PromiseFutureState::NotStarted => {
+                PromiseFutureState::NotStarted(_) => {
+                    let PromiseFutureState::NotStarted(promise) =
+                        std::mem::replace(&mut this.state, PromiseFutureState::Completed)
+                    else {
+                        unreachable!()
+                    };
                     let gc = ocaml_runtime();
-                    // Wrap the OCaml promise into a future that can be awaited.
-                    let wrapper = unsafe {
-                        olwti_wrap_lwt_future(
-                            &gc,
-                            this.promise
-                                .take()
-                                .expect("Promise does not have a value inside")
-                                .into_value(&gc)
-                                .expect("MlBox inside PromiseFuture is expected to be only reference"),
-                        )
+                    // Complete synchronously if the promise is already resolved
+                    // or rejected, which is common for cached values.
+                    let state = unsafe { olwti_lwt_peek(&gc, promise.as_value(&gc)) }
+                        .expect("olwti_lwt_peek has thrown an exception");
+                    match state {
+                        Some(Ok(value)) => return Poll::Ready(Ok(T::from_value(value))),
+                        Some(Err(msg)) => {
+                            return Poll::Ready(Err(
+                                crate::error::Error::LwtPromiseRejection(msg),
+                            ))
+                        }
+                        None => (),
                     }
-                    .expect("olwti_wrap_lwt_future has thrown an exception");
-                    let ml_box_future = wrapper.coerce().clone();
+                    // Wrap the OCaml promise into a future that can be awaited.
+                    let wrapper =
+                        unsafe { olwti_wrap_lwt_future(&gc, promise.into_value(&gc)) }
+                            .expect("olwti_wrap_lwt_future has thrown an exception");
 
-                    // Create a Rust future to await the OCaml future and process the result.
-                    let future = Box::pin(async move {
-                        let ml_box = ml_box_future.await?;
+                    // Transition to the waiting state.
+                    this.state = PromiseFutureState::Waiting(wrapper);
+                }
+                // Poll the OCaml future.
+                PromiseFutureState::Waiting(wrapper) => {
+                    let result = match wrapper.coerce().poll_value(cx) {
+                        Poll::Ready(result) => result,
+                        // If still pending, return `Poll::Pending`.
+                        Poll::Pending => return Poll::Pending,
+                    };
+                    // On completion, update state and return the result.
+                    this.state = PromiseFutureState::Completed;
+                    let result = result.map(|root| {
                         let gc = ocaml_runtime();
-                        let value = ml_box.into_value(&gc)
-                            .expect("MlBox from ml_box_future.await? is expected to be only reference");
-                        Ok(T::from_value(value))
+                        let value = root.into_value(&gc);
+                        T::from_value(value)
                     });
-
-                    // Transition to the running state.
-                    this.state = PromiseFutureState::Running(future);
+                    return Poll::Ready(result);
                 }
-                // Poll the running future.
-                PromiseFutureState::Running(fut) => match fut.as_mut().poll(cx) {
-                    // On completion, update state and return the result.
-                    Poll::Ready(result) => {
-                        this.state = PromiseFutureState::Completed;
-                        return Poll::Ready(result);
-                    }
-                    // If still pending, return `Poll::Pending`.
-                    Poll::Pending => return Poll::Pending,
-                },
                 // Panic if polled after completion.
                 PromiseFutureState::Completed => {
                     panic!("PromiseFuture polled after completion")
@@ -0,0 +1,214 @@
+//! Table of OCaml values kept alive by Rust.
+//!
+//! # Overview
+//!
+//! [`MlBox`] registers a generational global root on creation, and removes it
+//! on drop. Promises, resolvers and values of awaited promises mostly live for
+//! a single call, so with many calls per second this churn shows up in OCaml
+//! GC profiles.
+//!
+//! [`Root`] is a cheaper alternative for such short-lived values. Rooted
+//! values are stored in slots of a single OCaml array, which is the only
+//! global root, and `Root` is just an index of the slot. Freed slots are
+//! reused, and the array grows when all slots are in use.
+//!
+//! `Root` can be dropped on any thread. The slot is cleared and made available
+//! for reuse next time the table is accessed from OCaml domain, which happens
+//! at least once per executor tick.
+//!
+//! The table itself is only accessed with `&ocaml::Runtime` at hand, i.e. with
+//! OCaml domain lock held, which serializes the access, so reading a rooted
+//! value takes no lock of its own. Only the list of released slots is behind a
+//! `Mutex`, as `Root` may be dropped on any thread. Like the executor, the
+//! table belongs to the main domain (see `Rust_async.Runtime`).
+//!
+//! The array is shared with OCaml side, so that OCaml code can read rooted
+//! values by slot index, which is how [`crate::promise`] passes a batch of
+//! settlements to OCaml without holding unrooted values meanwhile.
+
+use std::{cell::UnsafeCell, sync::Mutex};
+
+use ocaml_rs_smartptr::ml_box::MlBox;
+
+// OCaml callbacks are registered in ../lib/Rust_async.ml
+ocaml::import! {
+    // `olwti_root_table_grow` replaces the OCaml side array with an array of
+    // `len` slots, copying the values, and returns the new array along with
+    // its length. Returns the current array if it already has `len` slots
+    fn olwti_root_table_grow(len: isize) -> (ocaml::Value, isize);
+}
+
+/// Number of slots in the table when it's first used.
+const INITIAL_CAPACITY: usize = 256;
+
+struct RootTable {
+    /// OCaml array holding rooted values, `None` until first use.
+    slots: Option<MlBox>,
+    capacity: usize,
+    /// Indices of empty slots.
+    free: Vec<u32>,
+}
+
+/// [`RootTable`] guarded by OCaml domain lock, see [module-level
+/// documentation](self).
+struct DomainLocked(UnsafeCell<RootTable>);
+
+// SAFETY: the table is only accessed via `DomainLocked::get`, which requires
+// OCaml domain lock to be held
+unsafe impl Sync for DomainLocked {}
+
+impl DomainLocked {
+    /// Returns the table, `gc` proves that OCaml domain lock is held.
+    ///
+    /// # Safety
+    ///
+    /// The returned reference must not be alive while calling into OCaml, as
+    /// finalisers may access the table.
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn get(&self, _gc: &ocaml::Runtime) -> &mut RootTable {
+        &mut *self.0.get()
+    }
+}
+
+static TABLE: DomainLocked = DomainLocked(UnsafeCell::new(RootTable {
+    slots: None,
+    capacity: 0,
+    free: Vec::new(),
+}));
+
+/// Indices of slots released by dropped [`Root`]s, which are not cleared yet.
+/// Kept apart from `TABLE`, as `Root` may be dropped on any thread, including
+/// threads not holding OCaml domain lock.
+static RELEASED: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+impl RootTable {
+    /// Clears the released slots and makes them available for reuse.
+    fn reclaim(&mut self, gc: &ocaml::Runtime) {
+        let released = std::mem::take(&mut *RELEASED.lock().unwrap());
+        if released.is_empty() {
+            return;
+        }
+        let mut slots = self.slots.as_ref().unwrap().as_value(gc);
+        for index in released {
+            unsafe { slots.store_field(gc, index as usize, ocaml::Value::unit()) };
+            self.free.push(index);
+        }
+    }
+
+    /// Stores `values` in free slots, returns `None` if there are not enough
+    /// free slots.
+    fn acquire<const N: usize>(
+        &mut self,
+        gc: &ocaml::Runtime,
+        values: [ocaml::Value; N],
+    ) -> Option<[Root; N]> {
+        self.reclaim(gc);
+        if self.free.len() < N {
+            return None;
+        }
+        let mut slots = self.slots.as_ref().unwrap().as_value(gc);
+        Some(values.map(|value| {
+            let index = self.free.pop().unwrap();
+            unsafe { slots.store_field(gc, index as usize, value) };
+            Root { index }
+        }))
+    }
+
+    /// Switches to `slots` of `capacity`, which is the array returned by
+    /// `olwti_root_table_grow`, with rooted values already copied.
+    fn grow(&mut self, slots: MlBox, capacity: usize) {
+        if capacity > self.capacity {
+            // Lower indices are handed out first
+            self.free
+                .extend((self.capacity as u32..capacity as u32).rev());
+            self.capacity = capacity;
+        }
+        self.slots = Some(slots);
+    }
+}
+
+/// A slot of the root table, keeping an OCaml value alive while `Root` is
+/// alive, see [module-level documentation](self).
+///
+/// Unlike [`MlBox`], `Root` does not register a global root of its own. Roots
+/// can only be created and read on the main domain, and dropped anywhere.
+#[derive(Debug)]
+pub struct Root {
+    index: u32,
+}
+
+impl Root {
+    /// Stores `value` in a free slot of the table, growing the table if
+    /// needed.
+    pub fn new(gc: &ocaml::Runtime, value: ocaml::Value) -> Root {
+        let [root] = Self::new_many(gc, [value]);
+        root
+    }
+
+    /// Stores `values` in free slots of the table, growing the table if
+    /// needed. Unlike calling [`Root::new`] for each value, the values which
+    /// are not rooted yet can't be moved by OCaml GC in between.
+    pub fn new_many<const N: usize>(
+        gc: &ocaml::Runtime,
+        values: [ocaml::Value; N],
+    ) -> [Root; N] {
+        // SAFETY: `acquire` does not call into OCaml
+        if let Some(roots) = unsafe { TABLE.get(gc) }.acquire(gc, values) {
+            return roots;
+        }
+        // Creating a bigger array allocates on OCaml heap, which may move
+        // `values`, or run finalisers using the table. So `values` are rooted,
+        // and the table is not borrowed meanwhile.
+        let values = values.map(|value| MlBox::new(gc, value));
+        loop {
+            let capacity = unsafe { TABLE.get(gc) }.capacity;
+            let new_capacity = (capacity * 2).max(INITIAL_CAPACITY).max(N);
+            let (slots, slots_len) =
+                unsafe { olwti_root_table_grow(gc, new_capacity as isize) }
+                    .expect("olwti_root_table_grow has thrown an exception");
+            // The table might have been grown further by a finaliser, the
+            // returned array is the current one anyway
+            let slots = MlBox::new(gc, slots);
+            // SAFETY: OCaml is not called until the next iteration
+            let table = unsafe { TABLE.get(gc) };
+            table.grow(slots, slots_len as usize);
+            let values = values.each_ref().map(|value| value.as_value(gc));
+            if let Some(roots) = table.acquire(gc, values) {
+                return roots;
+            }
+        }
+    }
+
+    /// Returns the rooted value.
+    pub fn as_value(&self, gc: &ocaml::Runtime) -> ocaml::Value {
+        let table = unsafe { TABLE.get(gc) };
+        let slots = table.slots.as_ref().unwrap().as_value(gc);
+        unsafe { slots.field(self.index as usize) }
+    }
+
+    /// Returns the index of the slot, which is also valid on OCaml side.
+    pub(crate) fn index(&self) -> usize {
+        self.index as usize
+    }
+
+    /// Returns the rooted value, and releases the slot.
+    pub fn into_value(self, gc: &ocaml::Runtime) -> ocaml::Value {
+        self.as_value(gc)
+    }
+}
+
+impl Drop for Root {
+    fn drop(&mut self) {
+        RELEASED.lock().unwrap().push(self.index);
+    }
+}
+
+/// Clears the slots released by dropped [`Root`]s, so that they do not keep
+/// values alive until the slots are reused. Called at the end of each executor
+/// tick.
+pub(crate) fn reclaim(gc: &ocaml::Runtime) {
+    let table = unsafe { TABLE.get(gc) };
+    if table.slots.is_some() {
+        table.reclaim(gc);
+    }
+}
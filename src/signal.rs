@@ -0,0 +1,98 @@
+//! Forwarding Unix signals from Tokio runtime to Lwt.
+//!
+//! # Overview
+//!
+//! Once the shared [Tokio runtime](crate::domain_executor::tokio_rt) is
+//! started, the kernel may deliver a process-directed signal to any of its
+//! worker threads, which makes `Lwt_unix.on_signal` unreliable. Instead,
+//! signals can be received through [`tokio::signal::unix`], which installs a
+//! process-wide handler and forwards signals to all listeners, no matter which
+//! thread has caught the signal.
+//!
+//! [`SignalListener`] is exposed to OCaml as `Rust_async.Signal.t`, OCaml side
+//! gets `next` function returning Lwt promise, which is resolved by a task
+//! running on the domain executor.
+//!
+//! Note that once a handler for a signal is installed by Tokio, it stays
+//! installed for the lifetime of the process, i.e. default action of the
+//! signal (such as terminating the process for `SIGTERM`) is not taken any
+//! more, even after all listeners are dropped.
+//!
+//! Signals require `tokio` feature. Without it, [`SignalListener::new`] fails
+//! with [`io::ErrorKind::Unsupported`], but as no extra threads are started in
+//! that case, `Lwt_unix.on_signal` can be used instead.
+
+use std::{ffi::c_int, io};
+
+#[cfg(feature = "tokio")]
+use std::sync::Arc;
+#[cfg(feature = "tokio")]
+use tokio::signal::unix::{signal, Signal, SignalKind};
+
+#[cfg(feature = "tokio")]
+use crate::domain_executor::tokio_rt;
+
+extern "C" {
+    /*
+    caml_convert_signal_number() converts OCaml signal number (such as
+    Sys.sigterm, which is negative) to the system one, system signal numbers
+    are returned as is.
+    */
+    fn caml_convert_signal_number(signo: c_int) -> c_int;
+}
+
+/// Converts OCaml signal number to the system one.
+pub(crate) fn convert_signal_number(signo: c_int) -> c_int {
+    unsafe { caml_convert_signal_number(signo) }
+}
+
+/// A listener of a Unix signal, backed by [`tokio::signal::unix::Signal`]
+/// registered on the shared Tokio runtime.
+#[derive(Clone, Debug)]
+pub struct SignalListener {
+    #[cfg(feature = "tokio")]
+    inner: Arc<tokio::sync::Mutex<Signal>>,
+}
+
+impl SignalListener {
+    /// Starts listening for signal `signo` (system signal number).
+    ///
+    /// Signals delivered before the listener was created are not observed.
+    #[cfg(feature = "tokio")]
+    pub fn new(signo: c_int) -> io::Result<Self> {
+        let _guard = tokio_rt().enter();
+        let signal = signal(SignalKind::from_raw(signo))?;
+        Ok(Self {
+            inner: Arc::new(tokio::sync::Mutex::new(signal)),
+        })
+    }
+
+    /// Starts listening for signal `signo` (system signal number).
+    ///
+    /// Always fails, as signals require `tokio` feature.
+    #[cfg(not(feature = "tokio"))]
+    pub fn new(signo: c_int) -> io::Result<Self> {
+        let _ = signo;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "signals require `tokio` feature of ocaml-lwt-interop",
+        ))
+    }
+
+    /// Waits for the next signal. Multiple deliveries of the signal, which
+    /// happened since the previous call, are coalesced into one.
+    ///
+    /// Returns `None` if no more signals will be received, i.e. Tokio runtime
+    /// has been shut down.
+    #[cfg(feature = "tokio")]
+    pub async fn recv(&self) -> Option<()> {
+        self.inner.lock().await.recv().await
+    }
+
+    /// Waits for the next signal, returns `None` right away, as signals
+    /// require `tokio` feature.
+    #[cfg(not(feature = "tokio"))]
+    pub async fn recv(&self) -> Option<()> {
+        None
+    }
+}
@@ -6,8 +6,16 @@ use ocaml_rs_smartptr::ocaml_gen_extras::PolymorphicValue;
 use ocaml_rs_smartptr::ptr::DynBox;
 use ocaml_rs_smartptr::{register_rtti, register_type};
 
-use crate::domain_executor::DomainExecutor;
+use crate::async_func::RustClosure;
+use crate::channel::MlChannel;
+use crate::domain_executor::{ocaml_runtime, spawn_with_runtime, DomainExecutor};
+use crate::lwt_bytes::LwtBytes;
+use crate::lwt_io::IoStream;
 use crate::ml_box_future::MlBoxFuture;
+use crate::promise::Promise;
+use crate::root_table::Root;
+use crate::signal::{self, SignalListener};
+use crate::sync;
 
 ///////////////////////////////////////////////////////////////////////////////
 //////////                       Promise                             //////////
@@ -24,7 +32,7 @@ pub fn lwti_mlbox_future_create() -> Future {
 #[ocaml_gen::func]
 #[ocaml::func]
 pub fn lwti_mlbox_future_resolve(fut: Future, value: PolymorphicValue<'a'>) {
-    fut.coerce().resolve(MlBox::new(gc, value.into()));
+    fut.coerce().resolve(Root::new(gc, value.into()));
 }
 
 #[ocaml_gen::func]
@@ -46,6 +54,22 @@ pub fn lwti_executor_create(notify_id: isize) -> Executor {
     DynBox::new_shared(executor)
 }
 
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_executor_create_single_threaded(
+    notify_id: isize,
+) -> Result<Executor, String> {
+    DomainExecutor::new_single_threaded(crate::notification::Notification(notify_id))
+        .map(DynBox::new_shared)
+        .map_err(|err| err.to_string())
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_executor_reactor_fd(executor: Executor) -> Option<isize> {
+    executor.coerce().reactor_fd().map(|fd| fd as isize)
+}
+
 #[ocaml_gen::func]
 #[ocaml::func]
 pub fn lwti_executor_run_pending(executor: Executor) {
@@ -53,6 +77,366 @@ pub fn lwti_executor_run_pending(executor: Executor) {
     ex.tick();
 }
 
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_executor_set_iteration_hooks(executor: Executor, enabled: bool) {
+    executor.coerce().set_iteration_hooks(enabled);
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_executor_enter_iter(executor: Executor) {
+    executor.coerce().enter_iter();
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_executor_leave_iter(executor: Executor) {
+    executor.coerce().leave_iter();
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_executor_notifications_sent(executor: Executor) -> isize {
+    executor.coerce().notifications_sent() as isize
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_executor_dump_tasks(executor: Executor) -> String {
+    executor.coerce().dump_tasks()
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_executor_set_task_tracking(executor: Executor, enabled: bool) {
+    executor.coerce().set_task_tracking(enabled);
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////                       Closure                             //////////
+///////////////////////////////////////////////////////////////////////////////
+
+pub type Closure = DynBox<RustClosure>;
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_closure_call(
+    closure: Closure,
+    args: PolymorphicValue<'a'>,
+) -> PolymorphicValue<'b'> {
+    let v = unsafe { closure.coerce().call(gc, args.into()) };
+    ocaml::FromValue::from_value(v)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////                       Channel                             //////////
+///////////////////////////////////////////////////////////////////////////////
+
+pub type Channel = DynBox<MlChannel>;
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_channel_create(capacity: isize) -> Channel {
+    DynBox::new_shared(MlChannel::bounded(capacity as usize))
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_channel_send(chan: Channel, value: PolymorphicValue<'a'>) -> Promise<()> {
+    let chan = chan.coerce().clone();
+    let value = MlBox::new(gc, value.into());
+    let (promise, resolver) = Promise::new(gc);
+    let task = spawn_with_runtime(gc, async move {
+        let res = chan.0.send(value).await;
+        let gc = &ocaml_runtime();
+        match res {
+            Ok(()) => resolver.resolve(gc, &()),
+            Err(err) => resolver.reject(gc, err.to_string()),
+        }
+    });
+    task.detach();
+    promise
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_channel_recv(chan: Channel) -> Promise<Option<PolymorphicValue<'a'>>> {
+    let chan = chan.coerce().clone();
+    let (promise, resolver) = Promise::new(gc);
+    let task = spawn_with_runtime(gc, async move {
+        let res = chan.0.recv().await;
+        let gc = &ocaml_runtime();
+        let value: Option<PolymorphicValue<'a'>> =
+            res.map(|ml_box| ocaml::FromValue::from_value(ml_box.as_value(gc)));
+        resolver.resolve(gc, &value);
+    });
+    task.detach();
+    promise
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_channel_close(chan: Channel) -> bool {
+    chan.coerce().0.close()
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_channel_is_closed(chan: Channel) -> bool {
+    chan.coerce().0.is_closed()
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////                          Io                               //////////
+///////////////////////////////////////////////////////////////////////////////
+
+pub type Io = DynBox<IoStream>;
+
+/// Converts offset and length of a buffer range passed from OCaml, the bounds
+/// are checked by [`IoStream`].
+fn io_range(off: isize, len: isize) -> std::io::Result<(usize, usize)> {
+    match (usize::try_from(off), usize::try_from(len)) {
+        (Ok(off), Ok(len)) => Ok((off, len)),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("negative offset {off} or length {len}"),
+        )),
+    }
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_io_read(stream: Io, buf: LwtBytes, off: isize, len: isize) -> Promise<isize> {
+    let (promise, resolver) = Promise::new(gc);
+    let task = spawn_with_runtime(gc, async move {
+        let res = match io_range(off, len) {
+            Ok((off, len)) => stream.coerce().read(&buf, off, len).await,
+            Err(err) => Err(err),
+        };
+        let gc = &ocaml_runtime();
+        match res {
+            Ok(n) => resolver.resolve(gc, &(n as isize)),
+            Err(err) => resolver.reject(gc, err.to_string()),
+        }
+    });
+    task.detach();
+    promise
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_io_write(
+    stream: Io,
+    buf: LwtBytes,
+    off: isize,
+    len: isize,
+) -> Promise<isize> {
+    let (promise, resolver) = Promise::new(gc);
+    let task = spawn_with_runtime(gc, async move {
+        let res = match io_range(off, len) {
+            Ok((off, len)) => stream.coerce().write(&buf, off, len).await,
+            Err(err) => Err(err),
+        };
+        let gc = &ocaml_runtime();
+        match res {
+            Ok(n) => resolver.resolve(gc, &(n as isize)),
+            Err(err) => resolver.reject(gc, err.to_string()),
+        }
+    });
+    task.detach();
+    promise
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_io_shutdown(stream: Io) -> Promise<()> {
+    let (promise, resolver) = Promise::new(gc);
+    let task = spawn_with_runtime(gc, async move {
+        let res = stream.coerce().shutdown().await;
+        let gc = &ocaml_runtime();
+        match res {
+            Ok(()) => resolver.resolve(gc, &()),
+            Err(err) => resolver.reject(gc, err.to_string()),
+        }
+    });
+    task.detach();
+    promise
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////                       Mutex                               //////////
+///////////////////////////////////////////////////////////////////////////////
+
+pub type Mutex = DynBox<sync::Mutex>;
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_mutex_create() -> Mutex {
+    DynBox::new_shared(sync::Mutex::new())
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_mutex_lock(mutex: Mutex) -> Promise<()> {
+    let mutex = mutex.coerce().clone();
+    let (promise, resolver) = Promise::new(gc);
+    let task = spawn_with_runtime(gc, async move {
+        mutex.ocaml_lock().await;
+        let gc = &ocaml_runtime();
+        resolver.resolve(gc, &());
+    });
+    task.detach();
+    promise
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_mutex_unlock(mutex: Mutex) -> bool {
+    mutex.coerce().ocaml_unlock()
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_mutex_is_locked(mutex: Mutex) -> bool {
+    mutex.coerce().try_lock().is_none()
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////                      Semaphore                            //////////
+///////////////////////////////////////////////////////////////////////////////
+
+pub type Semaphore = DynBox<sync::Semaphore>;
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_semaphore_max_permits() -> isize {
+    sync::Semaphore::MAX_PERMITS as isize
+}
+
+/// Converts the number of permits passed from OCaml, which must be within
+/// `0..=max`, as Tokio panics on overflowing permits.
+fn semaphore_permits(n: isize, max: usize) -> Result<usize, String> {
+    usize::try_from(n)
+        .ok()
+        .filter(|&n| n <= max)
+        .ok_or_else(|| format!("number of permits {n} is out of range 0..={max}"))
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_semaphore_create(permits: isize) -> Result<Semaphore, String> {
+    let permits = semaphore_permits(permits, sync::Semaphore::MAX_PERMITS)?;
+    Ok(DynBox::new_shared(sync::Semaphore::new(permits)))
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_semaphore_acquire(sem: Semaphore, n: isize) -> Promise<()> {
+    match semaphore_permits(n, u32::MAX as usize) {
+        Ok(n) => {
+            let sem = sem.coerce().clone();
+            let (promise, resolver) = Promise::new(gc);
+            let task = spawn_with_runtime(gc, async move {
+                // OCaml releases permits explicitly
+                sem.acquire(n as u32).await.forget();
+                let gc = &ocaml_runtime();
+                resolver.resolve(gc, &());
+            });
+            task.detach();
+            promise
+        }
+        Err(msg) => Promise::rejected(gc, msg),
+    }
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_semaphore_release(sem: Semaphore, n: isize) -> Result<(), String> {
+    let sem = sem.coerce();
+    let n = semaphore_permits(n, sync::Semaphore::MAX_PERMITS - sem.available_permits())?;
+    sem.release(n);
+    Ok(())
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_semaphore_available(sem: Semaphore) -> isize {
+    sem.coerce().available_permits() as isize
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////                        Notify                             //////////
+///////////////////////////////////////////////////////////////////////////////
+
+pub type Notify = DynBox<sync::Notify>;
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_notify_create() -> Notify {
+    DynBox::new_shared(sync::Notify::new())
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_notify_wait(notify: Notify) -> Promise<()> {
+    // Register as a waiter before returning to OCaml, so that
+    // `notify_waiters` called right after `wait` is not lost
+    let notified = notify.coerce().notified_owned();
+    let (promise, resolver) = Promise::new(gc);
+    let task = spawn_with_runtime(gc, async move {
+        notified.await;
+        let gc = &ocaml_runtime();
+        resolver.resolve(gc, &());
+    });
+    task.detach();
+    promise
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_notify_notify_one(notify: Notify) {
+    notify.coerce().notify_one()
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_notify_notify_waiters(notify: Notify) {
+    notify.coerce().notify_waiters()
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////                        Signal                             //////////
+///////////////////////////////////////////////////////////////////////////////
+
+pub type Signal = DynBox<SignalListener>;
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_signal_listen(signo: isize) -> Result<Signal, String> {
+    let signo = signal::convert_signal_number(signo as std::ffi::c_int);
+    SignalListener::new(signo)
+        .map(DynBox::new_shared)
+        .map_err(|err| err.to_string())
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_signal_next(listener: Signal) -> Promise<()> {
+    let listener = listener.coerce().clone();
+    let (promise, resolver) = Promise::new(gc);
+    let task = spawn_with_runtime(gc, async move {
+        let res = listener.recv().await;
+        let gc = &ocaml_runtime();
+        match res {
+            Some(()) => resolver.resolve(gc, &()),
+            None => resolver.reject(gc, "signal listener is closed".to_string()),
+        }
+    });
+    task.detach();
+    promise
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 //////////               Register Types & Traits                     //////////
 ///////////////////////////////////////////////////////////////////////////////
@@ -72,6 +456,55 @@ register_rtti! {
             object_safe_traits: [],
         }
     );
+    register_type!(
+        {
+            ty: crate::async_func::RustClosure,
+            marker_traits: [core::marker::Sync, core::marker::Send],
+            object_safe_traits: [],
+        }
+    );
+    register_type!(
+        {
+            ty: crate::channel::MlChannel,
+            marker_traits: [core::marker::Sync, core::marker::Send],
+            object_safe_traits: [],
+        }
+    );
+    register_type!(
+        {
+            ty: crate::lwt_io::IoStream,
+            marker_traits: [core::marker::Sync, core::marker::Send],
+            object_safe_traits: [],
+        }
+    );
+    register_type!(
+        {
+            ty: crate::sync::Mutex,
+            marker_traits: [core::marker::Sync, core::marker::Send],
+            object_safe_traits: [],
+        }
+    );
+    register_type!(
+        {
+            ty: crate::sync::Semaphore,
+            marker_traits: [core::marker::Sync, core::marker::Send],
+            object_safe_traits: [],
+        }
+    );
+    register_type!(
+        {
+            ty: crate::sync::Notify,
+            marker_traits: [core::marker::Sync, core::marker::Send],
+            object_safe_traits: [],
+        }
+    );
+    register_type!(
+        {
+            ty: crate::signal::SignalListener,
+            marker_traits: [core::marker::Sync, core::marker::Send],
+            object_safe_traits: [],
+        }
+    );
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -89,6 +522,66 @@ ocaml_gen_bindings! {
     decl_module!("Executor", {
         decl_type!(Executor => "t");
         decl_func!(lwti_executor_create => "create");
+        decl_func!(lwti_executor_create_single_threaded => "create_single_threaded");
+        decl_func!(lwti_executor_reactor_fd => "reactor_fd");
         decl_func!(lwti_executor_run_pending => "run_pending");
+        decl_func!(lwti_executor_set_iteration_hooks => "set_iteration_hooks");
+        decl_func!(lwti_executor_enter_iter => "enter_iter");
+        decl_func!(lwti_executor_leave_iter => "leave_iter");
+        decl_func!(lwti_executor_notifications_sent => "notifications_sent");
+        decl_func!(lwti_executor_dump_tasks => "dump_tasks");
+        decl_func!(lwti_executor_set_task_tracking => "set_task_tracking");
+    });
+
+    decl_module!("Closure", {
+        decl_type!(Closure => "t");
+        decl_func!(lwti_closure_call => "call");
+    });
+
+    decl_module!("Channel", {
+        decl_type!(Channel => "t");
+        decl_func!(lwti_channel_create => "create");
+        decl_func!(lwti_channel_send => "send");
+        decl_func!(lwti_channel_recv => "recv");
+        decl_func!(lwti_channel_close => "close");
+        decl_func!(lwti_channel_is_closed => "is_closed");
+    });
+
+    decl_module!("Io", {
+        decl_type!(Io => "t");
+        decl_func!(lwti_io_read => "read");
+        decl_func!(lwti_io_write => "write");
+        decl_func!(lwti_io_shutdown => "shutdown");
+    });
+
+    decl_module!("Mutex", {
+        decl_type!(Mutex => "t");
+        decl_func!(lwti_mutex_create => "create");
+        decl_func!(lwti_mutex_lock => "lock");
+        decl_func!(lwti_mutex_unlock => "unlock");
+        decl_func!(lwti_mutex_is_locked => "is_locked");
+    });
+
+    decl_module!("Semaphore", {
+        decl_type!(Semaphore => "t");
+        decl_func!(lwti_semaphore_max_permits => "max_permits");
+        decl_func!(lwti_semaphore_create => "create");
+        decl_func!(lwti_semaphore_acquire => "acquire");
+        decl_func!(lwti_semaphore_release => "release");
+        decl_func!(lwti_semaphore_available => "available");
+    });
+
+    decl_module!("Notify", {
+        decl_type!(Notify => "t");
+        decl_func!(lwti_notify_create => "create");
+        decl_func!(lwti_notify_wait => "wait");
+        decl_func!(lwti_notify_notify_one => "notify_one");
+        decl_func!(lwti_notify_notify_waiters => "notify_waiters");
+    });
+
+    decl_module!("Signal", {
+        decl_type!(Signal => "t");
+        decl_func!(lwti_signal_listen => "listen");
+        decl_func!(lwti_signal_next => "next");
     });
 }
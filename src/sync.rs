@@ -0,0 +1,203 @@
+//! Async synchronization primitives shared between OCaml and Rust.
+//!
+//! # Overview
+//!
+//! [`Mutex`], [`Semaphore`] and [`Notify`] are each backed by a single
+//! corresponding primitive from [`tokio::sync`], and are exposed to OCaml as
+//! `Rust_async.Mutex.t`, `Rust_async.Semaphore.t` and `Rust_async.Notify.t`.
+//! The same instance can be acquired with `Lwt.t` from OCaml and with `.await`
+//! from Rust, so a resource can be guarded consistently from both sides.
+//! Releasing on one side wakes up a waiter on the other side.
+//!
+//! Tokio synchronization primitives are runtime-agnostic, so Rust side can
+//! use them from tasks running on the
+//! [OCaml domain executor](crate::domain_executor::DomainExecutor), from Tokio
+//! tasks, or from any other thread.
+//!
+//! OCaml waiters are represented by tasks on OCaml domain executor, which
+//! resolve the promise once the primitive is acquired. Waiters are served in
+//! FIFO order regardless of the side they come from.
+
+use std::sync::Arc;
+
+use ocaml::{FromValue, ToValue};
+use ocaml_gen::OCamlDesc;
+use ocaml_rs_smartptr::ptr::DynBox;
+
+use crate::async_func::named_type_unique_id;
+
+/// Implements conversions for a type shared with OCaml via `DynBox<$ty>`, so
+/// that it can be used directly in stub signatures as `$desc`.
+macro_rules! shared_with_ocaml {
+    ($ty:ty, $desc:literal) => {
+        unsafe impl ocaml::ToValue for $ty {
+            fn to_value(&self, gc: &ocaml::Runtime) -> ocaml::Value {
+                DynBox::new_shared(self.clone()).to_value(gc)
+            }
+        }
+
+        unsafe impl ocaml::FromValue for $ty {
+            fn from_value(v: ocaml::Value) -> Self {
+                DynBox::<$ty>::from_value(v).coerce().clone()
+            }
+        }
+
+        impl OCamlDesc for $ty {
+            fn ocaml_desc(_env: &::ocaml_gen::Env, _generics: &[&str]) -> String {
+                $desc.to_string()
+            }
+
+            fn unique_id() -> u128 {
+                named_type_unique_id($desc)
+            }
+        }
+    };
+}
+
+/// A guard returned by [`Mutex::lock`], the mutex is unlocked when the guard
+/// is dropped.
+pub type MutexGuard = tokio::sync::OwnedMutexGuard<()>;
+
+/// An async mutex, backed by [`tokio::sync::Mutex`].
+///
+/// Unlike Rust mutexes, it does not protect any data, but rather a resource,
+/// which is accessed from both OCaml and Rust.
+#[derive(Clone, Debug, Default)]
+pub struct Mutex {
+    inner: Arc<tokio::sync::Mutex<()>>,
+    /// Guard of the lock acquired from OCaml side, OCaml does not have RAII
+    /// and unlocks the mutex explicitly.
+    ocaml_guard: Arc<std::sync::Mutex<Option<MutexGuard>>>,
+}
+
+impl Mutex {
+    /// Creates a new unlocked mutex.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Locks the mutex, waiting while it's locked by someone else.
+    pub async fn lock(&self) -> MutexGuard {
+        self.inner.clone().lock_owned().await
+    }
+
+    /// Attempts to lock the mutex without waiting.
+    pub fn try_lock(&self) -> Option<MutexGuard> {
+        self.inner.clone().try_lock_owned().ok()
+    }
+
+    /// Locks the mutex on behalf of OCaml side, the lock is held until
+    /// [`Mutex::ocaml_unlock`] is called.
+    pub(crate) async fn ocaml_lock(&self) {
+        let guard = self.lock().await;
+        *self.ocaml_guard.lock().unwrap() = Some(guard);
+    }
+
+    /// Unlocks the mutex, locked by OCaml side. Returns `false` if the mutex
+    /// was not locked by OCaml side.
+    pub(crate) fn ocaml_unlock(&self) -> bool {
+        self.ocaml_guard.lock().unwrap().take().is_some()
+    }
+}
+
+shared_with_ocaml!(Mutex, "Rust_async.Mutex.t");
+
+/// A permit returned by [`Semaphore::acquire`], permits are released back to
+/// the semaphore when it is dropped.
+pub type SemaphorePermit = tokio::sync::OwnedSemaphorePermit;
+
+/// An async counting semaphore, backed by [`tokio::sync::Semaphore`].
+#[derive(Clone, Debug)]
+pub struct Semaphore {
+    inner: Arc<tokio::sync::Semaphore>,
+}
+
+impl Semaphore {
+    /// The maximum number of permits a semaphore can hold, see
+    /// [`tokio::sync::Semaphore::MAX_PERMITS`].
+    pub const MAX_PERMITS: usize = tokio::sync::Semaphore::MAX_PERMITS;
+
+    /// Creates a new semaphore with the given number of permits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `permits` exceeds [`Semaphore::MAX_PERMITS`].
+    pub fn new(permits: usize) -> Self {
+        Self {
+            inner: Arc::new(tokio::sync::Semaphore::new(permits)),
+        }
+    }
+
+    /// Acquires `n` permits, waiting while there are not enough permits
+    /// available.
+    pub async fn acquire(&self, n: u32) -> SemaphorePermit {
+        self.inner
+            .clone()
+            .acquire_many_owned(n)
+            .await
+            .expect("Semaphore is never closed")
+    }
+
+    /// Attempts to acquire `n` permits without waiting.
+    pub fn try_acquire(&self, n: u32) -> Option<SemaphorePermit> {
+        self.inner.clone().try_acquire_many_owned(n).ok()
+    }
+
+    /// Adds `n` permits to the semaphore.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of available permits would exceed
+    /// [`Semaphore::MAX_PERMITS`].
+    pub fn release(&self, n: usize) {
+        self.inner.add_permits(n)
+    }
+
+    /// Returns the number of available permits.
+    pub fn available_permits(&self) -> usize {
+        self.inner.available_permits()
+    }
+}
+
+shared_with_ocaml!(Semaphore, "Rust_async.Semaphore.t");
+
+/// Notifies waiting tasks, backed by [`tokio::sync::Notify`].
+#[derive(Clone, Debug, Default)]
+pub struct Notify {
+    inner: Arc<tokio::sync::Notify>,
+}
+
+impl Notify {
+    /// Creates a new `Notify`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Waits for a notification, see [`tokio::sync::Notify::notified`].
+    pub async fn notified(&self) {
+        self.inner.notified().await
+    }
+
+    /// Returns a future waiting for a notification, which is registered as a
+    /// waiter right away, i.e. it will be woken up by
+    /// [`Notify::notify_waiters`], called before it's polled for the first
+    /// time.
+    pub fn notified_owned(&self) -> impl std::future::Future<Output = ()> + Send {
+        let mut notified = Box::pin(self.inner.clone().notified_owned());
+        notified.as_mut().enable();
+        notified
+    }
+
+    /// Notifies a single waiting task, or stores a permit for the next waiter
+    /// if there is none.
+    pub fn notify_one(&self) {
+        self.inner.notify_one()
+    }
+
+    /// Notifies all currently waiting tasks.
+    pub fn notify_waiters(&self) {
+        self.inner.notify_waiters()
+    }
+}
+
+shared_with_ocaml!(Notify, "Rust_async.Notify.t");
@@ -0,0 +1,189 @@
+//! Tracking of tasks spawned onto OCaml domain executor.
+//!
+//! # Overview
+//!
+//! When a promise returned by an async stub never resolves, the Rust task
+//! behind it is stuck somewhere, and it's not visible from OCaml side. Once
+//! tracking is enabled by [`DomainExecutor::set_task_tracking`] (or
+//! `Rust_async.Debug.set_task_tracking true` from OCaml side), every task
+//! spawned onto [`DomainExecutor`] is registered in its task list until the
+//! task completes or is cancelled, along with:
+//!
+//! - the name of the task, which is the stub function name for tasks spawned
+//!   by `#[ocaml_lwt_interop::func]` stubs,
+//! - the location, where the task has been spawned, spawn functions in
+//!   [`crate::domain_executor`] are `#[track_caller]`,
+//! - the age of the task, and the number of times it has been polled.
+//!
+//! [`DomainExecutor::tasks`] returns a snapshot of the list, and
+//! [`DomainExecutor::dump_tasks`] formats it as text, one task per line. From
+//! OCaml side, the same text is returned by `Rust_async.Debug.dump_tasks ()`.
+//!
+//! Tasks, which complete without suspending (see `#[ocaml_lwt_interop::func]`
+//! documentation), are never spawned, and thus are not tracked. Neither are
+//! tasks spawned while tracking is disabled.
+//!
+//! Tracking is disabled by default, as registering a task takes a lock and
+//! an allocation on each spawn, while a disabled registry only costs an atomic
+//! load.
+//!
+//! [`DomainExecutor`]: crate::domain_executor::DomainExecutor
+//! [`DomainExecutor::set_task_tracking`]: crate::domain_executor::DomainExecutor::set_task_tracking
+//! [`DomainExecutor::tasks`]: crate::domain_executor::DomainExecutor::tasks
+//! [`DomainExecutor::dump_tasks`]: crate::domain_executor::DomainExecutor::dump_tasks
+
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    panic::Location,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+/// Statistics of a live task, shared between the registry and the task.
+struct TaskStats {
+    name: Option<&'static str>,
+    location: &'static Location<'static>,
+    spawned_at: Instant,
+    polls: AtomicU64,
+}
+
+/// List of live tasks of an executor.
+#[derive(Default)]
+pub(crate) struct TaskRegistry {
+    enabled: AtomicBool,
+    next_id: AtomicU64,
+    tasks: Mutex<HashMap<u64, Arc<TaskStats>>>,
+}
+
+impl TaskRegistry {
+    /// Switches tracking of newly spawned tasks on or off.
+    pub(crate) fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns whether newly spawned tasks are tracked.
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Registers a task running `future` if tracking is enabled, the task is
+    /// removed from the list once the returned future is dropped.
+    pub(crate) fn track<F: Future>(
+        self: &Arc<Self>,
+        name: Option<&'static str>,
+        location: &'static Location<'static>,
+        future: F,
+    ) -> Tracked<F> {
+        if !self.is_enabled() {
+            return Tracked {
+                future,
+                entry: None,
+            };
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let stats = Arc::new(TaskStats {
+            name,
+            location,
+            spawned_at: Instant::now(),
+            polls: AtomicU64::new(0),
+        });
+        self.tasks.lock().unwrap().insert(id, stats.clone());
+        Tracked {
+            future,
+            entry: Some(TrackedEntry {
+                id,
+                stats,
+                registry: self.clone(),
+            }),
+        }
+    }
+
+    /// Returns a snapshot of the live tasks, oldest first.
+    pub(crate) fn snapshot(&self) -> Vec<TaskInfo> {
+        let now = Instant::now();
+        let mut tasks: Vec<TaskInfo> = self
+            .tasks
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&id, stats)| TaskInfo {
+                id,
+                name: stats.name,
+                location: stats.location,
+                age: now.saturating_duration_since(stats.spawned_at),
+                polls: stats.polls.load(Ordering::Relaxed),
+            })
+            .collect();
+        tasks.sort_by_key(|task| task.id);
+        tasks
+    }
+}
+
+/// A future of a task, counting polls of `future` if the task is tracked.
+pub(crate) struct Tracked<F> {
+    future: F,
+    entry: Option<TrackedEntry>,
+}
+
+/// Entry of a tracked task in the registry.
+struct TrackedEntry {
+    id: u64,
+    stats: Arc<TaskStats>,
+    registry: Arc<TaskRegistry>,
+}
+
+impl<F: Future> Future for Tracked<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // SAFETY: `future` is never moved out of `Tracked`, and `Tracked` does
+        // not implement `Unpin` unless `F` does
+        let this = unsafe { self.get_unchecked_mut() };
+        if let Some(entry) = &this.entry {
+            entry.stats.polls.fetch_add(1, Ordering::Relaxed);
+        }
+        unsafe { Pin::new_unchecked(&mut this.future) }.poll(cx)
+    }
+}
+
+impl Drop for TrackedEntry {
+    fn drop(&mut self) {
+        self.registry.tasks.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// A snapshot of a live task, see [module-level documentation](self).
+#[derive(Debug, Clone)]
+pub struct TaskInfo {
+    /// Task id, unique within the executor, ids grow in spawn order.
+    pub id: u64,
+    /// Name given on spawn, e.g. stub function name.
+    pub name: Option<&'static str>,
+    /// Location, where the task has been spawned.
+    pub location: &'static Location<'static>,
+    /// Time since the task has been spawned.
+    pub age: Duration,
+    /// Number of times the task has been polled.
+    pub polls: u64,
+}
+
+impl fmt::Display for TaskInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} {} at {}, age {:.3}s, polled {} times",
+            self.id,
+            self.name.unwrap_or("<unnamed>"),
+            self.location,
+            self.age.as_secs_f64(),
+            self.polls
+        )
+    }
+}
@@ -0,0 +1,62 @@
+//! Timers for tasks running on OCaml domain executor.
+//!
+//! # Overview
+//!
+//! [`sleep`] and [`timeout`] are built on top of `Lwt_unix.sleep`, i.e. timers
+//! are driven by Lwt event loop, and do not require Tokio runtime (see `tokio`
+//! feature). The sleeping task is woken up through the same machinery as any
+//! other task awaiting an OCaml [`crate::promise::Promise`].
+//!
+//! These functions must only be used from tasks running on
+//! [OCaml domain executor](crate::domain_executor::DomainExecutor). Tasks on
+//! Tokio runtime should use `tokio::time` instead.
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use ocaml_lwt_interop::time;
+//!
+//! #[ocaml_lwt_interop::func]
+//! pub fn my_delayed_answer() -> i64 {
+//!     time::sleep(Duration::from_millis(10)).await;
+//!     42
+//! }
+//! ```
+
+use std::{future::Future, time::Duration};
+
+use crate::domain_executor::ocaml_runtime;
+use crate::error::Error;
+use crate::promise::Promise;
+
+// OCaml callbacks are registered in ../lib/Rust_async.ml
+ocaml::import! {
+    // `olwti_lwt_sleep` calls `Lwt_unix.sleep`
+    fn olwti_lwt_sleep(seconds: f64) -> Promise<()>;
+}
+
+/// Waits until `duration` has elapsed, see [module-level
+/// documentation](self).
+///
+/// Dropping the returned future does not cancel underlying `Lwt_unix.sleep`,
+/// its promise is just ignored once resolved.
+pub async fn sleep(duration: Duration) {
+    let promise = {
+        let gc = &ocaml_runtime();
+        unsafe { olwti_lwt_sleep(gc, duration.as_secs_f64()) }
+            .expect("olwti_lwt_sleep has thrown an exception")
+    };
+    promise
+        .await
+        .expect("Lwt_unix.sleep promise has been rejected")
+}
+
+/// Awaits `fut` for at most `duration`. Returns [`Error::Timeout`] if
+/// `duration` has elapsed before `fut` completed, `fut` is dropped in this
+/// case.
+pub async fn timeout<F: Future>(duration: Duration, fut: F) -> Result<F::Output, Error> {
+    futures_lite::future::or(async { Ok(fut.await) }, async {
+        sleep(duration).await;
+        Err(Error::Timeout)
+    })
+    .await
+}
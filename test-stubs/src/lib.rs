@@ -1,11 +1,48 @@
 use async_task::Task;
 use futures_lite::future;
-use ocaml_lwt_interop::async_func::OCamlAsyncFunc;
+use ocaml_lwt_interop::async_func::{OCamlAsyncFunc, RustAsyncFunc};
+use ocaml_lwt_interop::channel::Channel;
 use ocaml_lwt_interop::domain_executor::{self, run_in_ocaml_domain, spawn};
+use ocaml_lwt_interop::lwt_bytes::PinnedBuffer;
+use ocaml_lwt_interop::lwt_io::{LwtReader, LwtWriter, Stream};
+use ocaml_lwt_interop::sync::{Mutex, Notify, Semaphore};
+use ocaml_lwt_interop::time;
 use ocaml_rs_smartptr::func::OCamlFunc;
 use ocaml_rs_smartptr::ocaml_gen_bindings;
+use ocaml_rs_smartptr::ptr::DynBox;
+use ocaml_rs_smartptr::{register_rtti, register_type};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
 use tokio::time::{sleep, Duration};
 
+/// Counts allocations made by each thread, used by allocation benchmarks.
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<i64> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn allocations() -> i64 {
+    ALLOCATIONS.with(|n| n.get())
+}
+
 #[ocaml_lwt_interop::func]
 #[ocaml_gen::func]
 pub fn lwti_tests_bench() -> () {
@@ -131,6 +168,439 @@ pub fn lwti_tests_await_promise(
     p.await.map_err(|e| e.to_string())
 }
 
+#[ocaml_lwt_interop::func]
+#[ocaml_gen::func]
+pub fn lwti_tests_channel_sum(count: i64) -> i64 {
+    let chan = Channel::bounded(1);
+    let sender = chan.sender();
+    let producer = tokio::spawn(async move {
+        for i in 1..=count {
+            sender.send(i).await.expect("channel closed unexpectedly");
+        }
+        sender.close();
+    });
+    let mut sum = 0;
+    while let Some(v) = chan.recv().await {
+        sum += v;
+    }
+    producer.await.unwrap();
+    sum
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_tests_io_echo() -> Stream {
+    let (client, server) = tokio::io::duplex(64);
+    domain_executor::tokio_rt().spawn(async move {
+        let (mut reader, mut writer) = tokio::io::split(server);
+        tokio::io::copy(&mut reader, &mut writer).await
+    });
+    Stream::new(client)
+}
+
+#[ocaml_lwt_interop::func]
+#[ocaml_gen::func]
+pub fn lwti_tests_io_copy(reader: LwtReader, writer: LwtWriter) -> Result<i64, String> {
+    let (mut reader, mut writer) = (reader, writer);
+    let n = tokio::io::copy(&mut reader, &mut writer)
+        .await
+        .map_err(|e| e.to_string())?;
+    writer.shutdown().await.map_err(|e| e.to_string())?;
+    Ok(n as i64)
+}
+
+#[ocaml_lwt_interop::func]
+#[ocaml_gen::func]
+pub fn lwti_tests_bytes_uppercase(buf: PinnedBuffer) -> PinnedBuffer {
+    domain_executor::tokio_rt()
+        .spawn(async move {
+            let mut buf = buf;
+            buf.make_ascii_uppercase();
+            buf
+        })
+        .await
+        .unwrap()
+}
+
+#[ocaml_lwt_interop::func]
+#[ocaml_gen::func]
+pub fn lwti_tests_mutex_lock(mutex: Mutex) -> () {
+    let _guard = mutex.lock().await;
+    sleep(Duration::from_millis(1)).await;
+}
+
+#[ocaml_lwt_interop::func]
+#[ocaml_gen::func]
+pub fn lwti_tests_semaphore_acquire(sem: Semaphore) -> () {
+    let _permit = sem.acquire(2).await;
+    sleep(Duration::from_millis(1)).await;
+}
+
+#[ocaml_lwt_interop::func]
+#[ocaml_gen::func]
+pub fn lwti_tests_notify_wait(notify: Notify) -> () {
+    notify.notified().await
+}
+
+#[ocaml_lwt_interop::func]
+#[ocaml_gen::func]
+pub fn lwti_tests_pattern_args((a, b): (i64, i64), mut acc: i64) -> i64 {
+    future::yield_now().await;
+    acc += a * b;
+    acc
+}
+
+#[ocaml_lwt_interop::func(tokio)]
+#[ocaml_gen::func]
+pub fn lwti_tests_tokio_sum(n: i64) -> i64 {
+    sleep(Duration::from_millis(1)).await;
+    (1..=n).sum()
+}
+
+#[ocaml_lwt_interop::func(blocking)]
+#[ocaml_gen::func]
+pub fn lwti_tests_blocking_sleep(ms: i64) -> i64 {
+    std::thread::sleep(std::time::Duration::from_millis(ms as u64));
+    ms
+}
+
+#[ocaml_lwt_interop::func]
+#[ocaml_gen::func]
+pub fn lwti_tests_lwt_timers(ms: i64) -> bool {
+    time::sleep(Duration::from_millis(ms as u64)).await;
+    let fast = time::timeout(Duration::from_secs(5), future::ready(())).await;
+    let slow = time::timeout(
+        Duration::from_millis(1),
+        time::sleep(Duration::from_secs(5)),
+    );
+    fast.is_ok() && slow.await.is_err()
+}
+
+#[ocaml_lwt_interop::func]
+#[ocaml_gen::func]
+pub fn lwti_tests_async_io_timer(ms: i64) -> i64 {
+    async_io::Timer::after(Duration::from_millis(ms as u64)).await;
+    let handle = domain_executor::handle();
+    domain_executor::async_io_rt()
+        .spawn(async move {
+            async_io::Timer::after(Duration::from_millis(ms as u64)).await;
+            // Driver thread is registered with OCaml runtime
+            unsafe { run_in_ocaml_domain(&handle, |_gc| ms * 2) }
+        })
+        .await
+}
+
+#[ocaml_lwt_interop::func]
+#[ocaml_gen::func]
+pub fn lwti_tests_tcp_echo_thread() -> bool {
+    use tokio::io::AsyncReadExt;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        sleep(Duration::from_millis(5)).await;
+        let mut buf = [0u8; 5];
+        socket.read_exact(&mut buf).await.unwrap();
+        socket.write_all(&buf).await.unwrap();
+        std::thread::current().id()
+    });
+    let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+    client.write_all(b"hello").await.unwrap();
+    let mut buf = [0u8; 5];
+    client.read_exact(&mut buf).await.unwrap();
+    let server_thread = server.await.unwrap();
+    // Returns `true` if the server task was running on OCaml domain thread
+    &buf == b"hello" && server_thread == std::thread::current().id()
+}
+
+#[ocaml_lwt_interop::func]
+#[ocaml_gen::func]
+pub fn lwti_tests_await_allocations(f: OCamlAsyncFunc<(), ()>, n: i64) -> i64 {
+    // Allocations of the stubs called by OCaml on the same thread are counted
+    // as well
+    let before = allocations();
+    for _ in 0..n {
+        f.call(()).await.unwrap();
+    }
+    allocations() - before
+}
+
+#[ocaml_lwt_interop::func]
+#[ocaml_gen::func]
+pub fn lwti_tests_bench_ready() -> () {}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_tests_batch_resolve(n: i64) -> Vec<ocaml_lwt_interop::promise::Promise<i64>> {
+    use ocaml_lwt_interop::domain_executor::{ocaml_runtime, spawn_with_runtime};
+    let (promises, resolvers): (Vec<_>, Vec<_>) = (0..n)
+        .map(|_| ocaml_lwt_interop::promise::Promise::new(gc))
+        .unzip();
+    // All promises are settled within one tick, even ones are resolved and
+    // odd ones are rejected
+    let task = spawn_with_runtime(gc, async move {
+        future::yield_now().await;
+        let gc = &ocaml_runtime();
+        for (i, resolver) in (0..).zip(resolvers) {
+            if i % 2 == 0 {
+                resolver.resolve(gc, &i);
+            } else {
+                resolver.reject(gc, format!("odd: {}", i));
+            }
+        }
+    });
+    task.detach();
+    promises
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_tests_promise_join(
+    promises: Vec<ocaml_lwt_interop::promise::Promise<()>>,
+) -> ocaml_lwt_interop::promise::Promise<()> {
+    ocaml_lwt_interop::promise::join(gc, promises)
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_tests_promise_pick(
+    promises: Vec<ocaml_lwt_interop::promise::Promise<i64>>,
+) -> ocaml_lwt_interop::promise::Promise<i64> {
+    ocaml_lwt_interop::promise::pick(gc, promises)
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_tests_promise_both(
+    a: ocaml_lwt_interop::promise::Promise<i64>,
+    b: ocaml_lwt_interop::promise::Promise<i64>,
+) -> ocaml_lwt_interop::promise::Promise<(i64, i64)> {
+    ocaml_lwt_interop::promise::both(gc, a, b)
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_tests_promise_all(
+    promises: Vec<ocaml_lwt_interop::promise::Promise<i64>>,
+) -> ocaml_lwt_interop::promise::Promise<Vec<i64>> {
+    ocaml_lwt_interop::promise::all(gc, promises)
+}
+
+#[ocaml_lwt_interop::func]
+#[ocaml_gen::func]
+pub fn lwti_tests_promise_choose(
+    promises: Vec<ocaml_lwt_interop::promise::Promise<i64>>,
+) -> Result<i64, String> {
+    let chosen = {
+        let gc = &domain_executor::ocaml_runtime();
+        ocaml_lwt_interop::promise::choose(gc, promises)
+    };
+    chosen.await.map_err(|err| err.to_string())
+}
+
+#[ocaml_lwt_interop::func]
+#[ocaml_gen::func]
+pub fn lwti_tests_yield_until(f: OCamlFunc<(), bool>) -> i64 {
+    // Spins until `f` returns `true`, which requires Lwt event loop to run
+    let mut yields = 0;
+    loop {
+        let finished = f.call(&domain_executor::ocaml_runtime(), ());
+        if finished {
+            break yields;
+        }
+        domain_executor::yield_to_lwt().await;
+        yields += 1;
+    }
+}
+
+#[ocaml_lwt_interop::func(max_concurrent = 1)]
+#[ocaml_gen::func]
+pub fn lwti_tests_limited_wait(p: ocaml_lwt_interop::promise::Promise<i64>) -> i64 {
+    p.await.unwrap_or(-1)
+}
+
+#[ocaml_lwt_interop::func(max_concurrent = 1, overload = "reject")]
+#[ocaml_gen::func]
+pub fn lwti_tests_limited_reject(p: ocaml_lwt_interop::promise::Promise<i64>) -> i64 {
+    p.await.unwrap_or(-1)
+}
+
+static BLOCKING_FLAG: (std::sync::Mutex<bool>, std::sync::Condvar) =
+    (std::sync::Mutex::new(false), std::sync::Condvar::new());
+
+#[ocaml_lwt_interop::blocking]
+#[ocaml_gen::func]
+pub fn lwti_tests_blocking_wait() -> bool {
+    let (flag, cond) = &BLOCKING_FLAG;
+    let guard = flag.lock().unwrap();
+    let (mut guard, _) = cond
+        .wait_timeout_while(guard, Duration::from_secs(5), |set| !*set)
+        .unwrap();
+    std::mem::replace(&mut *guard, false)
+}
+
+#[ocaml::func]
+#[ocaml_gen::func]
+pub fn lwti_tests_blocking_signal() {
+    let (flag, cond) = &BLOCKING_FLAG;
+    *flag.lock().unwrap() = true;
+    cond.notify_all();
+}
+
+#[ocaml_lwt_interop::func(err = "raise")]
+#[ocaml_gen::func]
+pub fn lwti_tests_result_raise(val: i64) -> Result<i64, String> {
+    future::yield_now().await;
+    if val < 0 {
+        return Err(format!("negative value: {}", val));
+    }
+    Ok(val)
+}
+
+// Test callbacks are registered in ../../test/test.ml
+ocaml::import! {
+    fn lwti_tests_make_error(msg: String) -> ocaml::Value;
+}
+
+/// Rust counterpart of `Test_error` exception from ../../test/test.ml
+pub struct TestError(String);
+
+unsafe impl ocaml::ToValue for TestError {
+    fn to_value(&self, gc: &ocaml::Runtime) -> ocaml::Value {
+        unsafe { lwti_tests_make_error(gc, self.0.clone()) }
+            .expect("lwti_tests_make_error has thrown an exception")
+    }
+}
+
+#[ocaml_lwt_interop::func(err = "exn")]
+#[ocaml_gen::func]
+pub fn lwti_tests_result_exn(msg: String) -> Result<(), TestError> {
+    future::yield_now().await;
+    Err(TestError(msg))
+}
+
+#[ocaml_lwt_interop::func]
+#[ocaml_gen::func]
+pub fn lwti_tests_result_call(
+    f: OCamlAsyncFunc<(i64,), Result<i64, String>>,
+    val: i64,
+) -> Result<i64, String> {
+    let res = f.call_result((val,)).await?;
+    Ok(res * 2)
+}
+
+pub struct Counter {
+    value: AtomicI64,
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_tests_counter_create() -> DynBox<Counter> {
+    DynBox::new_shared(Counter {
+        value: AtomicI64::new(0),
+    })
+}
+
+#[ocaml_lwt_interop::methods(prefix = "lwti_tests_counter")]
+impl Counter {
+    pub async fn add(&self, n: i64) -> i64 {
+        sleep(Duration::from_millis(1)).await;
+        self.value.fetch_add(n, Ordering::SeqCst) + n
+    }
+
+    pub async fn get(&self) -> i64 {
+        future::yield_now().await;
+        self.value.load(Ordering::SeqCst)
+    }
+}
+
+#[ocaml_lwt_interop::ocaml_trait(ocaml_type = "Test_backend.t")]
+#[allow(async_fn_in_trait)]
+pub trait Backend {
+    async fn fetch(&self, key: String) -> Result<String, String>;
+    async fn store(&self, key: String, value: String) -> Result<(), String>;
+}
+
+#[ocaml_lwt_interop::func]
+#[ocaml_gen::func]
+pub fn lwti_tests_backend_store(
+    backend: OCamlBackend,
+    key: String,
+    value: String,
+) -> Result<(), String> {
+    // Call the backend from Tokio task, call should be routed back onto OCaml
+    // domain executor
+    domain_executor::tokio_rt()
+        .spawn(async move { backend.store(key, value).await })
+        .await
+        .unwrap()
+}
+
+#[ocaml_lwt_interop::func]
+#[ocaml_gen::func]
+pub fn lwti_tests_backend_fetch(
+    backend: OCamlBackend,
+    key: String,
+) -> Result<String, String> {
+    domain_executor::tokio_rt()
+        .spawn(async move { backend.fetch(key).await })
+        .await
+        .unwrap()
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_tests_closure_adder(n: i64) -> RustAsyncFunc<(i64,), i64> {
+    RustAsyncFunc::new(move |(x,)| async move {
+        future::yield_now().await;
+        x + n
+    })
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_tests_closure_concat() -> RustAsyncFunc<(String, String), String> {
+    RustAsyncFunc::new(|(a, b)| async move {
+        sleep(Duration::from_millis(1)).await;
+        a + &b
+    })
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_tests_closure_counter() -> RustAsyncFunc<(), i64> {
+    let counter = Arc::new(AtomicI64::new(0));
+    RustAsyncFunc::new(move |()| {
+        let counter = counter.clone();
+        async move { counter.fetch_add(1, Ordering::SeqCst) + 1 }
+    })
+}
+
+#[ocaml_gen::func]
+#[ocaml::func]
+pub fn lwti_tests_closure_wrap(
+    f: OCamlAsyncFunc<(i64,), i64>,
+) -> RustAsyncFunc<(i64,), Result<i64, String>> {
+    RustAsyncFunc::new(move |(x,)| {
+        let f = f.clone();
+        async move {
+            let res = f.call((x,)).await.map_err(|e| e.to_string())?;
+            Ok(res * 10)
+        }
+    })
+}
+
+register_rtti! {
+    register_type!(
+        {
+            ty: crate::Counter,
+            marker_traits: [core::marker::Sync, core::marker::Send],
+            object_safe_traits: [],
+        }
+    );
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 //////////               OCaml bindings generation                   //////////
 ///////////////////////////////////////////////////////////////////////////////
@@ -147,5 +617,41 @@ ocaml_gen_bindings! {
         decl_func!(lwti_tests_promise_create => "promise_create");
         decl_func!(lwti_tests_promise_create_err => "promise_create_err");
         decl_func!(lwti_tests_await_promise => "await_promise");
+        decl_func!(lwti_tests_channel_sum => "channel_sum");
+        decl_func!(lwti_tests_io_echo => "io_echo");
+        decl_func!(lwti_tests_io_copy => "io_copy");
+        decl_func!(lwti_tests_bytes_uppercase => "bytes_uppercase");
+        decl_func!(lwti_tests_mutex_lock => "mutex_lock");
+        decl_func!(lwti_tests_semaphore_acquire => "semaphore_acquire");
+        decl_func!(lwti_tests_notify_wait => "notify_wait");
+        decl_func!(lwti_tests_pattern_args => "pattern_args");
+        decl_func!(lwti_tests_tokio_sum => "tokio_sum");
+        decl_func!(lwti_tests_blocking_sleep => "blocking_sleep");
+        decl_func!(lwti_tests_blocking_wait => "blocking_wait");
+        decl_func!(lwti_tests_blocking_signal => "blocking_signal");
+        decl_func!(lwti_tests_lwt_timers => "lwt_timers");
+        decl_func!(lwti_tests_async_io_timer => "async_io_timer");
+        decl_func!(lwti_tests_tcp_echo_thread => "tcp_echo_thread");
+        decl_func!(lwti_tests_await_allocations => "await_allocations");
+        decl_func!(lwti_tests_bench_ready => "bench_ready");
+        decl_func!(lwti_tests_batch_resolve => "batch_resolve");
+        decl_func!(lwti_tests_promise_join => "promise_join");
+        decl_func!(lwti_tests_promise_pick => "promise_pick");
+        decl_func!(lwti_tests_promise_both => "promise_both");
+        decl_func!(lwti_tests_promise_all => "promise_all");
+        decl_func!(lwti_tests_promise_choose => "promise_choose");
+        decl_func!(lwti_tests_yield_until => "yield_until");
+        decl_func!(lwti_tests_limited_wait => "limited_wait");
+        decl_func!(lwti_tests_limited_reject => "limited_reject");
+        decl_func!(lwti_tests_result_raise => "result_raise");
+        decl_func!(lwti_tests_result_exn => "result_exn");
+        decl_func!(lwti_tests_result_call => "result_call");
+        decl_func!(lwti_tests_counter_create => "counter_create");
+        decl_func!(lwti_tests_backend_store => "backend_store");
+        decl_func!(lwti_tests_backend_fetch => "backend_fetch");
+        decl_func!(lwti_tests_closure_adder => "closure_adder");
+        decl_func!(lwti_tests_closure_concat => "closure_concat");
+        decl_func!(lwti_tests_closure_counter => "closure_counter");
+        decl_func!(lwti_tests_closure_wrap => "closure_wrap");
     });
 }
@@ -1,3 +1,17 @@
+module Counter = struct
+  type tags =
+    [ `Ocaml_lwt_interop_test_stubs_counter
+    | `Core_marker_sync
+    | `Core_marker_send
+    ]
+
+  type 'a t' = ([> tags ] as 'a) Ocaml_rs_smartptr.Rusty_obj.t
+  type t = tags t'
+
+  external add : _ t' -> int64 -> int64 Lwt.t = "lwti_tests_counter_add"
+  external get : _ t' -> int64 Lwt.t = "lwti_tests_counter_get"
+end
+
 module Tests = struct
   external bench : unit -> unit Lwt.t = "lwti_tests_bench"
   external test_1 : unit -> unit Lwt.t = "lwti_tests_test1"
@@ -18,4 +32,109 @@ module Tests = struct
     :  int64 Lwt.t
     -> (int64, string) result Lwt.t
     = "lwti_tests_await_promise"
+
+  external channel_sum : int64 -> int64 Lwt.t = "lwti_tests_channel_sum"
+  external io_echo : unit -> Rust_async.Io.stream = "lwti_tests_io_echo"
+
+  external io_copy
+    :  Rust_async.Io.reader
+    -> Rust_async.Io.writer
+    -> (int64, string) result Lwt.t
+    = "lwti_tests_io_copy"
+
+  external bytes_uppercase
+    :  Lwt_bytes.t
+    -> Lwt_bytes.t Lwt.t
+    = "lwti_tests_bytes_uppercase"
+
+  external mutex_lock : Rust_async.Mutex.t -> unit Lwt.t = "lwti_tests_mutex_lock"
+
+  external semaphore_acquire
+    :  Rust_async.Semaphore.t
+    -> unit Lwt.t
+    = "lwti_tests_semaphore_acquire"
+
+  external notify_wait : Rust_async.Notify.t -> unit Lwt.t = "lwti_tests_notify_wait"
+
+  external pattern_args
+    :  int64 * int64
+    -> int64
+    -> int64 Lwt.t
+    = "lwti_tests_pattern_args"
+
+  external tokio_sum : int64 -> int64 Lwt.t = "lwti_tests_tokio_sum"
+  external blocking_sleep : int64 -> int64 Lwt.t = "lwti_tests_blocking_sleep"
+  external blocking_wait : unit -> bool = "lwti_tests_blocking_wait"
+  external blocking_signal : unit -> unit = "lwti_tests_blocking_signal"
+  external lwt_timers : int64 -> bool Lwt.t = "lwti_tests_lwt_timers"
+  external async_io_timer : int64 -> int64 Lwt.t = "lwti_tests_async_io_timer"
+  external tcp_echo_thread : unit -> bool Lwt.t = "lwti_tests_tcp_echo_thread"
+
+  external await_allocations
+    :  (unit -> unit Lwt.t)
+    -> int64
+    -> int64 Lwt.t
+    = "lwti_tests_await_allocations"
+  external bench_ready : unit -> unit Lwt.t = "lwti_tests_bench_ready"
+  external batch_resolve : int64 -> int64 Lwt.t array = "lwti_tests_batch_resolve"
+  external promise_join : unit Lwt.t array -> unit Lwt.t = "lwti_tests_promise_join"
+  external promise_pick : int64 Lwt.t array -> int64 Lwt.t = "lwti_tests_promise_pick"
+
+  external promise_both
+    :  int64 Lwt.t
+    -> int64 Lwt.t
+    -> (int64 * int64) Lwt.t
+    = "lwti_tests_promise_both"
+  external promise_all : int64 Lwt.t array -> int64 array Lwt.t = "lwti_tests_promise_all"
+
+  external promise_choose
+    :  int64 Lwt.t array
+    -> (int64, string) result Lwt.t
+    = "lwti_tests_promise_choose"
+  external yield_until : (unit -> bool) -> int64 Lwt.t = "lwti_tests_yield_until"
+  external limited_wait : int64 Lwt.t -> int64 Lwt.t = "lwti_tests_limited_wait"
+  external limited_reject : int64 Lwt.t -> int64 Lwt.t = "lwti_tests_limited_reject"
+  external result_raise : int64 -> int64 Lwt.t = "lwti_tests_result_raise"
+  external result_exn : string -> unit Lwt.t = "lwti_tests_result_exn"
+
+  external result_call
+    :  (int64 -> (int64, string) result Lwt.t)
+    -> int64
+    -> (int64, string) result Lwt.t
+    = "lwti_tests_result_call"
+
+  external counter_create : unit -> _ Counter.t' = "lwti_tests_counter_create"
+
+  external backend_store
+    :  Test_backend.t
+    -> string
+    -> string
+    -> (unit, string) result Lwt.t
+    = "lwti_tests_backend_store"
+
+  external backend_fetch
+    :  Test_backend.t
+    -> string
+    -> (string, string) result Lwt.t
+    = "lwti_tests_backend_fetch"
+
+  external closure_adder
+    :  int64
+    -> (int64 -> int64 Lwt.t) Rust_async.Closure.fn
+    = "lwti_tests_closure_adder"
+
+  external closure_concat
+    :  unit
+    -> (string -> string -> string Lwt.t) Rust_async.Closure.fn
+    = "lwti_tests_closure_concat"
+
+  external closure_counter
+    :  unit
+    -> (unit -> int64 Lwt.t) Rust_async.Closure.fn
+    = "lwti_tests_closure_counter"
+
+  external closure_wrap
+    :  (int64 -> int64 Lwt.t)
+    -> (int64 -> (int64, string) result Lwt.t) Rust_async.Closure.fn
+    = "lwti_tests_closure_wrap"
 end
@@ -0,0 +1,6 @@
+(* Record of functions, implementing `Backend` trait from
+   ../test-stubs/src/lib.rs *)
+type t =
+  { fetch : string -> (string, string) Lwt_result.t
+  ; store : string -> string -> (unit, string) Lwt_result.t
+  }
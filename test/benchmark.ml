@@ -1,13 +1,13 @@
 open Stubs
 
-let main_rust () =
+let main_rust ?(label = "Rust+Lwt") ?(bench = Tests.bench) () =
   print_endline "";
-  print_endline "running Lwt+Rust test";
+  Printf.printf "running Lwt+Rust test [%s]\n" label;
   let start = Unix.gettimeofday () in
   let pause = Lwt_unix.auto_pause 0.1 in
   let page = ref 0 in
   let rec aux x =
-    let%lwt () = Tests.bench () in
+    let%lwt () = bench () in
     page := x;
     let%lwt () = pause () in
     aux (x + 1)
@@ -18,9 +18,11 @@ let main_rust () =
   let%lwt () = Lwt_unix.sleep 10.0 in
   let finish = Unix.gettimeofday () in
   Printf.printf
-    "%.3f iterations per second, %d iterations total [Rust+Lwt]\n"
+    "%.3f iterations per second, %d iterations total, %d notifications sent [%s]\n"
     (float_of_int !page /. (finish -. start))
-    !page;
+    !page
+    (Rust_async.Runtime.notifications_sent ())
+    label;
   print_endline "lwt main returning";
   Lwt.return ()
 ;;
@@ -88,6 +90,50 @@ let main_gc () =
   fut
 ;;
 
+let main_allocs () =
+  print_endline "";
+  print_endline "running allocations per await test";
+  let n = 100_000 in
+  let%lwt allocations =
+    Tests.await_allocations (fun () -> Lwt.pause ()) (Int64.of_int n)
+  in
+  Printf.printf
+    "%.2f allocations per await, %d awaits total [Rust allocations]\n"
+    (Int64.to_float allocations /. float_of_int n)
+    n;
+  print_endline "test main returning";
+  Lwt.return ()
+;;
+
+let main_roots () =
+  print_endline "";
+  print_endline "running GC stats test";
+  let n = 100_000 in
+  (* Each call roots a promise, a resolver and an awaited value *)
+  let rec aux x =
+    if x = n
+    then Lwt.return ()
+    else (
+      let%lwt _ = Tests.await_promise (Tests.spawn_lwt (Int64.of_int x)) in
+      aux (x + 1))
+  in
+  Gc.compact ();
+  let before = Gc.quick_stat () in
+  let start = Unix.gettimeofday () in
+  let%lwt () = aux 0 in
+  let finish = Unix.gettimeofday () in
+  let after = Gc.quick_stat () in
+  Printf.printf
+    "%.3f iterations per second, %d minor collections, %d major collections, %.0f \
+     minor words per iteration [GC stats]\n"
+    (float_of_int n /. (finish -. start))
+    (after.minor_collections - before.minor_collections)
+    (after.major_collections - before.major_collections)
+    ((after.minor_words -. before.minor_words) /. float_of_int n);
+  print_endline "test main returning";
+  Lwt.return ()
+;;
+
 let main_lwt () =
   print_endline "";
   print_endline "running Lwt-only baseline";
@@ -142,10 +188,22 @@ let () =
     (match Sys.argv with
      | [| _; "lwt" |] -> main_lwt ()
      | [| _; "rust" |] -> main_rust ()
+     | [| _; "rust-hooks" |] ->
+       Rust_async.Runtime.init ~iteration_hooks:true ();
+       main_rust ~label:"Rust+Lwt, iteration hooks" ()
+     | [| _; "rust-ready" |] ->
+       main_rust
+         ~label:"Rust+Lwt, completing without suspending"
+         ~bench:Tests.bench_ready
+         ()
      | [| _; "rust-slow" |] -> main_rust_slow ()
      | [| _; "gc" |] -> main_gc ()
+     | [| _; "allocs" |] -> main_allocs ()
+     | [| _; "roots" |] -> main_roots ()
      | [| _; "sync" |] -> main_sync ()
      | [| _ |] ->
-       failwith "no command provided on command line - should be one of: lwt, rust, gc"
+       failwith
+         "no command provided on command line - should be one of: lwt, rust, \
+          rust-hooks, rust-ready, gc, allocs, roots"
      | _ -> failwith "unknown command line arguments")
 ;;
@@ -69,6 +69,465 @@ let test_promise_to_rust_err _ () =
   | Error _ -> Lwt.return_unit
 ;;
 
+let test_promise_to_rust_resolved _ () =
+  Tests.await_promise (Lwt.return 3L)
+  >>= fun res ->
+  check (result int64 string) "resolved" (Ok 3L) res;
+  Tests.await_promise (Lwt.fail (Failure "err"))
+  >>= function
+  | Ok _ -> fail "expected error"
+  | Error _ -> Lwt.return_unit
+;;
+
+let test_func_ready _ () =
+  (match Lwt.state (Tests.bench_ready ()) with
+   | Lwt.Return () -> ()
+   | _ -> fail "expected resolved promise");
+  (match Lwt.state (Tests.await_promise (Lwt.return 3L)) with
+   | Lwt.Return (Ok 3L) -> ()
+   | _ -> fail "expected resolved promise");
+  (* [bench] yields, so it falls back to spawning a task *)
+  let p = Tests.bench () in
+  check bool "pending" true (Lwt.state p = Lwt.Sleep);
+  p
+;;
+
+let test_batch_resolve _ () =
+  let promises = Tests.batch_resolve 100L in
+  let order = ref [] in
+  let settled i _ = order := i :: !order in
+  Array.iteri (fun i p -> Lwt.on_any p (settled i) (settled i)) promises;
+  let ignore_result p = Lwt.catch (fun () -> p >|= ignore) (fun _ -> Lwt.return_unit) in
+  Lwt.join (Array.to_list (Array.map ignore_result promises))
+  >|= fun () ->
+  check (list int) "order" (List.init 100 Fun.id) (List.rev !order);
+  Array.iteri
+    (fun i p ->
+      match Lwt.state p with
+      | Lwt.Return v -> check int64 "value" (Int64.of_int i) v
+      | Lwt.Fail (Failure msg) -> check string "message" (Printf.sprintf "odd: %d" i) msg
+      | _ -> fail "expected settled promise")
+    promises
+;;
+
+let test_root_table _ () =
+  (* More pending promises than the initial capacity of the root table, values
+     have to survive its growth and compaction *)
+  let promises = List.init 1000 (fun i -> Tests.promise_create (Int64.of_int i)) in
+  Gc.compact ();
+  Lwt.all promises
+  >|= fun values ->
+  Gc.compact ();
+  check (list int64) "values" (List.init 1000 Int64.of_int) values
+;;
+
+let test_promise_combinators _ () =
+  let p1, r1 = Lwt.task () in
+  let p2, _ = Lwt.task () in
+  let picked = Tests.promise_pick [| p1; p2 |] in
+  Lwt.wakeup r1 1L;
+  picked
+  >>= fun v ->
+  check int64 "pick" 1L v;
+  check bool "pick cancels" true (Lwt.state p2 = Lwt.Fail Lwt.Canceled);
+  (* Resolving cancelled Rust promise later is ignored *)
+  Tests.promise_pick [| Tests.promise_create 2L; Lwt.return 3L |]
+  >>= fun v ->
+  check int64 "pick resolved" 3L v;
+  Tests.promise_both (Tests.spawn_lwt 1L) (Lwt.return 5L)
+  >>= fun (a, b) ->
+  check (pair int64 int64) "both" (2L, 5L) (a, b);
+  Tests.promise_all [| Tests.spawn_lwt 1L; Lwt.return 7L; Tests.spawn_lwt 3L |]
+  >>= fun values ->
+  check (array int64) "all" [| 2L; 7L; 4L |] values;
+  Tests.promise_join [| Lwt.pause (); Tests.bench () |]
+  >>= fun () ->
+  let p3, r3 = Lwt.task () in
+  let chosen = Tests.promise_choose [| p3; Tests.spawn_lwt 9L |] in
+  chosen
+  >>= fun v ->
+  check (result int64 string) "choose" (Ok 10L) v;
+  check bool "choose leaves pending" true (Lwt.state p3 = Lwt.Sleep);
+  Lwt.wakeup r3 0L;
+  Lwt.return_unit
+;;
+
+let test_yield_to_lwt _ () =
+  (* Rust task spins until the timer fires, which requires Lwt event loop to
+     poll for I/O in between *)
+  let fired = ref false in
+  Lwt.async (fun () -> Lwt_unix.sleep 0.01 >|= fun () -> fired := true);
+  Tests.yield_until (fun () -> !fired)
+  >|= fun yields -> check bool "yielded" true (yields > 0L)
+;;
+
+let test_max_concurrent _ () =
+  (* Bodies run one at a time in call order, whatever order the awaited
+     promises are resolved in *)
+  let tasks = List.init 3 (fun _ -> Lwt.task ()) in
+  let order = ref [] in
+  let calls =
+    List.map
+      (fun (p, _) -> Tests.limited_wait p >|= fun v -> order := v :: !order)
+      tasks
+  in
+  List.iteri (fun i (_, r) -> Lwt.wakeup r (Int64.of_int (i + 1))) (List.rev tasks);
+  Lwt.join calls
+  >>= fun () ->
+  check (list int64) "fifo" [ 3L; 2L; 1L ] (List.rev !order);
+  let p1, r1 = Lwt.task () in
+  let running = Tests.limited_reject p1 in
+  let rejected = Tests.limited_reject (Lwt.return 2L) in
+  check bool "rejected" true (Lwt.state rejected = Lwt.Fail Rust_async.Overloaded);
+  Lwt.wakeup r1 1L;
+  running
+  >>= fun v ->
+  check int64 "running" 1L v;
+  Tests.limited_reject (Lwt.return 3L) >|= fun v -> check int64 "after release" 3L v
+;;
+
+let live_tasks name =
+  (* Each line is "#<id> <name> at <location>, ..." *)
+  Rust_async.Debug.dump_tasks ()
+  |> String.split_on_char '\n'
+  |> List.filter (fun line ->
+    match String.split_on_char ' ' line with
+    | _ :: task_name :: "at" :: _ -> task_name = name
+    | _ -> false)
+  |> List.length
+;;
+
+let test_dump_tasks _ () =
+  Rust_async.Debug.set_task_tracking true;
+  let p, r = Lwt.task () in
+  let awaiting = Tests.await_promise p in
+  check int "live task" 1 (live_tasks "lwti_tests_await_promise");
+  Lwt.wakeup r 1L;
+  awaiting
+  >>= fun v ->
+  check (result int64 string) "value" (Ok 1L) v;
+  check int "completed task" 0 (live_tasks "lwti_tests_await_promise");
+  Rust_async.Debug.set_task_tracking false;
+  let p, r = Lwt.task () in
+  let awaiting = Tests.await_promise p in
+  check int "untracked task" 0 (live_tasks "lwti_tests_await_promise");
+  Lwt.wakeup r 2L;
+  awaiting
+  >>= fun v ->
+  check (result int64 string) "untracked value" (Ok 2L) v;
+  Lwt.return_unit
+;;
+
+let test_channel _ () =
+  let ch = Rust_async.Channel.create 1 in
+  let producer =
+    Lwt_list.iter_s (fun v -> Rust_async.Channel.send ch v) [ 1; 2; 3 ]
+    >|= fun () -> Rust_async.Channel.close ch
+  in
+  let rec consume acc =
+    Rust_async.Channel.recv ch
+    >>= function
+    | Some v -> consume (v :: acc)
+    | None -> Lwt.return (List.rev acc)
+  in
+  consume []
+  >>= fun values ->
+  producer
+  >>= fun () ->
+  check (list int) "values" [ 1; 2; 3 ] values;
+  Lwt.catch
+    (fun () -> Rust_async.Channel.send ch 4 >>= fun () -> fail "expected exn")
+    (fun _ -> Lwt.return_unit)
+;;
+
+let test_channel_from_rust _ () =
+  Tests.channel_sum 100L
+  >>= fun sum ->
+  check int64 "sum" 5050L sum;
+  Lwt.return_unit
+;;
+
+let test_io_echo _ () =
+  let ic, oc = Rust_async.Io.channels_of_stream (Tests.io_echo ()) in
+  Lwt_io.write_line oc "hello"
+  >>= fun () ->
+  Lwt_io.flush oc
+  >>= fun () ->
+  Lwt_io.read_line ic
+  >>= fun line ->
+  check string "echo" "hello" line;
+  Lwt_io.close oc
+;;
+
+let test_io_copy _ () =
+  let input = Lwt_io.of_bytes ~mode:Lwt_io.input (Lwt_bytes.of_string "hello") in
+  let output_buf = Lwt_bytes.create 5 in
+  let output = Lwt_io.of_bytes ~mode:Lwt_io.output output_buf in
+  Tests.io_copy
+    (Rust_async.Io.reader_of_channel input)
+    (Rust_async.Io.writer_of_channel output)
+  >>= function
+  | Ok n ->
+    check int64 "copied" 5L n;
+    check string "output" "hello" (Lwt_bytes.to_string output_buf);
+    Lwt.return_unit
+  | Error msg -> fail msg
+;;
+
+let test_bytes_zero_copy _ () =
+  let buf = Lwt_bytes.of_string "hello" in
+  Tests.bytes_uppercase buf
+  >>= fun buf' ->
+  check string "returned" "HELLO" (Lwt_bytes.to_string buf');
+  check string "in place" "HELLO" (Lwt_bytes.to_string buf);
+  Lwt.return_unit
+;;
+
+let test_mutex _ () =
+  let m = Rust_async.Mutex.create () in
+  Rust_async.Mutex.lock m
+  >>= fun () ->
+  let rust_locked = Tests.mutex_lock m in
+  Lwt.pause ()
+  >>= fun () ->
+  check bool "rust is waiting" true (Lwt.is_sleeping rust_locked);
+  Rust_async.Mutex.unlock m;
+  rust_locked
+  >>= fun () ->
+  check bool "unlocked" false (Rust_async.Mutex.is_locked m);
+  Rust_async.Mutex.with_lock m (fun () ->
+    check bool "locked" true (Rust_async.Mutex.is_locked m);
+    Lwt.return_unit)
+;;
+
+let test_semaphore _ () =
+  let s = Rust_async.Semaphore.create 2 in
+  Rust_async.Semaphore.acquire ~n:2 s
+  >>= fun () ->
+  let rust_acquired = Tests.semaphore_acquire s in
+  Lwt.pause ()
+  >>= fun () ->
+  check bool "rust is waiting" true (Lwt.is_sleeping rust_acquired);
+  Rust_async.Semaphore.release ~n:2 s;
+  rust_acquired
+  >>= fun () ->
+  check int "available" 2 (Rust_async.Semaphore.available s);
+  Lwt.return_unit
+;;
+
+let test_semaphore_permits _ () =
+  let module S = Rust_async.Semaphore in
+  let invalid f =
+    match f () with
+    | _ -> false
+    | exception Invalid_argument _ -> true
+  in
+  check bool "create negative" true (invalid (fun () -> S.create (-1)));
+  let s = S.create 0 in
+  check bool "acquire negative" true (invalid (fun () -> S.acquire ~n:(-1) s));
+  check bool "release negative" true (invalid (fun () -> S.release ~n:(-1) s));
+  S.release ~n:S.max_permits s;
+  check bool "release overflow" true (invalid (fun () -> S.release s));
+  check int "available" S.max_permits (S.available s);
+  Lwt.return_unit
+;;
+
+let test_notify _ () =
+  let n = Rust_async.Notify.create () in
+  let rust_waiting = Tests.notify_wait n in
+  Rust_async.Notify.notify_one n;
+  rust_waiting
+  >>= fun () ->
+  let ocaml_waiting = Rust_async.Notify.wait n in
+  Rust_async.Notify.notify_waiters n;
+  ocaml_waiting
+;;
+
+let test_signal _ () =
+  let t = Rust_async.Signal.listen Sys.sigusr1 in
+  Unix.kill (Unix.getpid ()) Sys.sigusr1;
+  Rust_async.Signal.next t
+  >>= fun () ->
+  let waiting = Rust_async.Signal.wait Sys.sigusr2 in
+  Unix.kill (Unix.getpid ()) Sys.sigusr2;
+  waiting
+;;
+
+let test_pattern_args _ () =
+  Tests.pattern_args (6L, 7L) 0L
+  >>= fun v ->
+  check int64 "value" 42L v;
+  Lwt.return_unit
+;;
+
+let test_tokio_mode _ () =
+  Tests.tokio_sum 100L
+  >>= fun v ->
+  check int64 "sum" 5050L v;
+  (* Lwt must keep running while the blocking body is executed *)
+  let ticks = ref 0 in
+  let sleeping = Tests.blocking_sleep 50L in
+  let rec loop () =
+    if Lwt.is_sleeping sleeping
+    then (
+      incr ticks;
+      Lwt_unix.sleep 0.005 >>= loop)
+    else Lwt.return_unit
+  in
+  loop ()
+  >>= fun () ->
+  sleeping
+  >>= fun v ->
+  check int64 "slept" 50L v;
+  check bool "lwt was not blocked" true (!ticks > 1);
+  Lwt.return_unit
+;;
+
+let test_blocking_section _ () =
+  (* The waiting thread releases the domain lock, so the main thread can signal it *)
+  let waiting = Lwt_preemptive.detach Tests.blocking_wait () in
+  Lwt_unix.sleep 0.01
+  >>= fun () ->
+  Tests.blocking_signal ();
+  waiting
+  >>= fun signalled ->
+  check bool "signalled" true signalled;
+  Lwt.return_unit
+;;
+
+let test_lwt_timers _ () =
+  let started = Unix.gettimeofday () in
+  Tests.lwt_timers 20L
+  >>= fun ok ->
+  check bool "timeout" true ok;
+  check bool "slept" true (Unix.gettimeofday () -. started >= 0.02);
+  Lwt.return_unit
+;;
+
+let test_async_io _ () =
+  Tests.async_io_timer 10L
+  >>= fun v ->
+  check int64 "value" 20L v;
+  Lwt.return_unit
+;;
+
+let test_tcp_echo_thread _ () =
+  Tests.tcp_echo_thread ()
+  >>= fun same_thread ->
+  check bool "server runs on Tokio worker" false same_thread;
+  Lwt.return_unit
+;;
+
+exception Test_error of string
+
+let () = Callback.register "lwti_tests_make_error" (fun msg -> Test_error msg)
+
+let test_result_raise _ () =
+  Tests.result_raise 42L
+  >>= fun v ->
+  check int64 "value" 42L v;
+  Lwt.catch
+    (fun () -> Tests.result_raise (-1L) >>= fun _ -> fail "expected exception")
+    (function
+      | Failure msg ->
+        check string "message" "negative value: -1" msg;
+        Lwt.return_unit
+      | exn -> Lwt.fail exn)
+;;
+
+let test_result_exn _ () =
+  Lwt.catch
+    (fun () -> Tests.result_exn "boom" >>= fun () -> fail "expected exception")
+    (function
+      | Test_error msg ->
+        check string "message" "boom" msg;
+        Lwt.return_unit
+      | exn -> Lwt.fail exn)
+;;
+
+let test_result_call _ () =
+  Tests.result_call (fun v -> Lwt_result.return (Int64.add v 1L)) 20L
+  >>= fun res ->
+  check (result int64 string) "ok" (Ok 42L) res;
+  Tests.result_call (fun _ -> Lwt_result.fail "err") 20L
+  >>= fun res ->
+  check (result int64 string) "error" (Error "err") res;
+  Tests.result_call (fun _ -> Lwt.fail (Failure "rejected")) 20L
+  >>= function
+  | Ok _ -> fail "expected error"
+  | Error _ -> Lwt.return_unit
+;;
+
+let test_methods _ () =
+  let counter = Tests.counter_create () in
+  Lwt.join
+    [ (Counter.add counter 1L >|= fun _ -> ())
+    ; (Counter.add counter 2L >|= fun _ -> ())
+    ; (Counter.add counter 3L >|= fun _ -> ())
+    ]
+  >>= fun () ->
+  Counter.get counter
+  >>= fun v ->
+  check int64 "value" 6L v;
+  Lwt.return_unit
+;;
+
+let test_ocaml_trait _ () =
+  let table = Hashtbl.create 8 in
+  let backend =
+    { Test_backend.fetch =
+        (fun key ->
+          Lwt.pause ()
+          >>= fun () ->
+          match Hashtbl.find_opt table key with
+          | Some value -> Lwt_result.return value
+          | None -> Lwt_result.fail "not found")
+    ; store =
+        (fun key value ->
+          Hashtbl.replace table key value;
+          Lwt_result.return ())
+    }
+  in
+  Tests.backend_store backend "key" "value"
+  >>= fun res ->
+  check (result unit string) "stored" (Ok ()) res;
+  Tests.backend_fetch backend "key"
+  >>= fun res ->
+  check (result string string) "found" (Ok "value") res;
+  Tests.backend_fetch backend "other"
+  >>= fun res ->
+  check (result string string) "not found" (Error "not found") res;
+  let failing = { backend with fetch = (fun _ -> Lwt.fail (Failure "boom")) } in
+  Tests.backend_fetch failing "key"
+  >>= function
+  | Ok _ -> fail "expected error"
+  | Error _ -> Lwt.return_unit
+;;
+
+let test_closures _ () =
+  let add_one = Tests.closure_adder 1L in
+  let add_two = Tests.closure_adder 2L in
+  Lwt.both (add_one 40L) (add_two 40L)
+  >>= fun (a, b) ->
+  check int64 "add_one" 41L a;
+  check int64 "add_two" 42L b;
+  let concat = Tests.closure_concat () in
+  concat "foo" "bar"
+  >>= fun s ->
+  check string "concat" "foobar" s;
+  let counter = Tests.closure_counter () in
+  counter ()
+  >>= fun _ ->
+  counter ()
+  >>= fun n ->
+  check int64 "counter" 2L n;
+  let wrapped = Tests.closure_wrap (fun x -> Lwt.return (Int64.succ x)) in
+  wrapped 4L
+  >>= fun res ->
+  check (result int64 string) "wrapped" (Ok 50L) res;
+  Lwt.return_unit
+;;
+
 let () =
   Lwt_main.run
     (run
@@ -84,6 +543,36 @@ let () =
            ; test_case "promise_from_rust" `Quick test_promise_from_rust
            ; test_case "promise_to_rust" `Quick test_promise_to_rust
            ; test_case "promise_to_rust_err" `Quick test_promise_to_rust_err
+           ; test_case "promise_to_rust_resolved" `Quick test_promise_to_rust_resolved
+           ; test_case "func_ready" `Quick test_func_ready
+           ; test_case "batch_resolve" `Quick test_batch_resolve
+           ; test_case "root_table" `Quick test_root_table
+           ; test_case "promise_combinators" `Quick test_promise_combinators
+           ; test_case "yield_to_lwt" `Quick test_yield_to_lwt
+           ; test_case "max_concurrent" `Quick test_max_concurrent
+           ; test_case "dump_tasks" `Quick test_dump_tasks
+           ; test_case "channel" `Quick test_channel
+           ; test_case "channel_from_rust" `Quick test_channel_from_rust
+           ; test_case "io_echo" `Quick test_io_echo
+           ; test_case "io_copy" `Quick test_io_copy
+           ; test_case "bytes_zero_copy" `Quick test_bytes_zero_copy
+           ; test_case "mutex" `Quick test_mutex
+           ; test_case "semaphore" `Quick test_semaphore
+           ; test_case "semaphore_permits" `Quick test_semaphore_permits
+           ; test_case "notify" `Quick test_notify
+           ; test_case "signal" `Quick test_signal
+           ; test_case "pattern_args" `Quick test_pattern_args
+           ; test_case "tokio_mode" `Quick test_tokio_mode
+           ; test_case "blocking_section" `Quick test_blocking_section
+           ; test_case "lwt_timers" `Quick test_lwt_timers
+           ; test_case "async_io" `Quick test_async_io
+           ; test_case "tcp_echo_thread" `Quick test_tcp_echo_thread
+           ; test_case "result_raise" `Quick test_result_raise
+           ; test_case "result_exn" `Quick test_result_exn
+           ; test_case "result_call" `Quick test_result_call
+           ; test_case "methods" `Quick test_methods
+           ; test_case "ocaml_trait" `Quick test_ocaml_trait
+           ; test_case "closures" `Quick test_closures
            ] )
        ])
 ;;
@@ -0,0 +1,85 @@
+open Alcotest
+open Alcotest_lwt
+open Lwt.Infix
+open Stubs
+
+(* Runtime has to be initialized before any Rust stubs are used, so
+   single-threaded and iteration hooks modes are tested in a separate
+   executable. Timer interval is increased, so that progress of tasks
+   relying on the timer is noticeable *)
+let () =
+  Rust_async.Runtime.init
+    ~single_threaded:true
+    ~timer_interval:0.05
+    ~iteration_hooks:true
+    ()
+;;
+
+let test_tcp_echo_thread _ () =
+  Tests.tcp_echo_thread ()
+  >>= fun same_thread ->
+  check bool "server runs on OCaml domain thread" true same_thread;
+  Lwt.return_unit
+;;
+
+let test_lwt_timers _ () =
+  Tests.lwt_timers 10L
+  >>= fun ok ->
+  check bool "timeout" true ok;
+  Lwt.return_unit
+;;
+
+let test_ping_pong _ () =
+  let rec loop n =
+    if n = 0 then Lwt.return_unit else Tests.bench () >>= fun () -> loop (n - 1)
+  in
+  loop 1000
+  >>= fun () ->
+  check
+    bool
+    "most wake-ups do not send notifications"
+    true
+    (Rust_async.Runtime.notifications_sent () < 100);
+  Lwt.return_unit
+;;
+
+let test_tokio_spawn _ () =
+  (* Each call spawns a Tokio task from a task on domain executor, which has to
+     run without waiting for the timer *)
+  let started = Unix.gettimeofday () in
+  let rec loop n =
+    if n = 0
+    then Lwt.return_unit
+    else Tests.handle_test Lwt.pause >>= fun () -> loop (n - 1)
+  in
+  loop 100
+  >>= fun () ->
+  check bool "not driven by timer" true (Unix.gettimeofday () -. started < 2.5);
+  Lwt.return_unit
+;;
+
+let test_io_echo _ () =
+  let ic, oc = Rust_async.Io.channels_of_stream (Tests.io_echo ()) in
+  Lwt_io.write_line oc "hello"
+  >>= fun () ->
+  Lwt_io.flush oc
+  >>= fun () ->
+  Lwt_io.read_line ic
+  >>= fun line ->
+  check string "echo" "hello" line;
+  Lwt_io.close oc
+;;
+
+let () =
+  Lwt_main.run
+    (run
+       "ocaml-lwt-interop-single-threaded"
+       [ ( "basic"
+         , [ test_case "tcp_echo_thread" `Quick test_tcp_echo_thread
+           ; test_case "lwt_timers" `Quick test_lwt_timers
+           ; test_case "ping_pong" `Quick test_ping_pong
+           ; test_case "tokio_spawn" `Quick test_tokio_spawn
+           ; test_case "io_echo" `Quick test_io_echo
+           ] )
+       ])
+;;